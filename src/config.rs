@@ -1,52 +1,411 @@
+use mdbook_renderer::config::BookConfig;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(default, rename = "kebab-case")]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
 pub struct Config {
     pub prelude: Option<PathBuf>,
+    #[serde(alias = "prelude_str")]
     pub prelude_str: Option<String>,
+    pub prelude_mode: Option<PreludeMode>,
+    /// use the default prelude when the `prelude` file can't be read
+    pub prelude_fallback: bool,
+    /// file the typst output is written to, relative to the build directory
+    pub output_file: Option<PathBuf>,
+    /// write the top level chapters to their own files included by the output
+    pub split_by_chapter: bool,
+    /// name of the chapter files, with `{number}` and `{slug}` in it
+    pub chapter_filename_template: Option<String>,
+    /// compile the output into a pdf after writing it
+    pub compile_pdf: bool,
+    /// typst program used to compile the pdf
+    pub typst_bin: Option<PathBuf>,
+    /// typst file included as is after the prelude
+    pub front_matter: Option<PathBuf>,
+    /// markdown file rendered as an unnumbered chapter after the book contents
+    pub changelog: Option<PathBuf>,
+    /// typst file included as is after the book contents
+    pub back_matter: Option<PathBuf>,
+    /// font family of the text in the default prelude
+    pub font: Option<String>,
+    /// size of the text in the default prelude
+    pub font_size: Option<String>,
+    /// set the document title and language for the pdf tags in the default prelude
+    pub tagged_pdf: bool,
+    /// start with a title page from the book title and authors in the default prelude
+    pub title_page: Option<bool>,
+    /// paper size of the pages in the default prelude
+    pub paper: Option<String>,
+    /// number the pages of the book contents from 1 in the default prelude
+    pub reset_page_numbering: Option<bool>,
+    /// page numbering of the outline pages in the default prelude
+    pub front_matter_numbering: Option<String>,
+    /// figure caption position in the default prelude
+    pub caption_position: Option<CaptionPosition>,
+    /// where the heading numbers are shown in the default prelude
+    pub heading_number_position: Option<NumberPosition>,
+    /// space between the lines of a paragraph in the default prelude
+    pub line_spacing: Option<String>,
+    /// show the page numbers as "Page X of Y" in the default prelude
+    pub page_x_of_y: bool,
+    /// hyphenate the words at the line ends in the default prelude
+    pub hyphenate: Option<bool>,
+    /// word before the image figure numbers in the default prelude
+    pub figure_supplement: Option<String>,
+    /// size of the text in the tables in the default prelude
+    pub table_font_size: Option<String>,
+    /// width of the code blocks in the default prelude
+    pub code_block_width: Option<String>,
+    /// show the language of the code blocks in their corner in the default prelude
+    pub code_lang_badge: bool,
+    /// wrap the long lines of the code blocks in the default prelude
+    pub code_wrap: bool,
+    /// typst color of the links in the default prelude
+    pub link_color: Option<String>,
+    /// underline the links in the default prelude
+    pub link_underline: Option<bool>,
+    /// language of the code blocks written to the output as typst markup
+    pub passthrough_lang: Option<String>,
+    /// lines of the code blocks starting with it are left out
+    pub hidden_line_prefix: Option<String>,
+    /// language of the code blocks without one
+    pub default_code_lang: Option<String>,
+    /// guess the language of the code blocks without one from their code
+    pub detect_code_lang: bool,
+    /// put the code blocks in figures with their language as the caption
+    pub code_lang_caption: bool,
+    /// show the prompt lines of the `console` code blocks in bold
+    pub style_console: bool,
+    /// collapse the runs of blank lines in the generated file
+    pub compact_output: bool,
+    /// use a `— Author` line at the end of a block quote as its attribution
+    pub quote_attribution: bool,
+    /// render `$..$` and `$$..$$` as typst math
+    pub math: bool,
+    /// render `^text^` as superscript and `~text~` as subscript
+    pub sub_superscript: bool,
+    /// render pandoc style `==text==` as highlighted text
+    pub pandoc_highlight: bool,
+    /// use the first `# ` title of a chapter as its title and demote the rest
+    pub demote_extra_h1: bool,
+    /// mark the separators of the summary with a `<separator>` label
+    pub separator_marker: bool,
+    /// start the chapter numbers from 1 again in each part
+    pub restart_numbering_per_part: bool,
+    /// heading numbering pattern after an appendix part title, `A.1` by
+    /// default
+    pub appendix_numbering: Option<String>,
+    /// show the tab groups of mdbook-tabs one after another
+    pub render_tabs: bool,
+    /// move the tables to the next page instead of splitting them
+    pub table_avoid_break: bool,
+    /// number of spaces, or a typst length, a tab in body text is replaced with
+    pub tab_width: Option<String>,
+    /// image extensions to convert, mapped to the format to convert them to
+    pub image_convert: Option<HashMap<String, String>>,
+    /// program used as `<program> <input> <output>` to convert images
+    pub image_converter: Option<PathBuf>,
+    /// copy the linked local files into the `assets` directory of the build
+    pub bundle_assets: bool,
+    /// refer to the headings for the links to them without their own text
+    pub auto_link_text: bool,
+    /// fail the build when a heading label is used more than once
+    pub strict_labels: bool,
+    /// how the heading labels are made from their text, to match the links
+    pub slug_style: Option<SlugStyle>,
+    /// delimiter after the numbers of the ordered lists
+    pub enum_delimiter: Option<EnumDelimiter>,
+    /// style of the rest of a checked task list item
+    pub completed_task_style: Option<TaskStyle>,
+    /// version of typst the output is for, newer features are avoided
+    /// for the older versions
+    pub typst_version: Option<String>,
+    /// values to start the counters from, to continue a previous document
+    pub counter_offsets: Option<HashMap<String, usize>>,
 }
 
-impl Default for Config {
-    fn default() -> Config {
-        Config {
-            prelude: None,
-            prelude_str: None,
-        }
-    }
+/// Which of `prelude` and `prelude-str` is used when both are given
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PreludeMode {
+    /// only the contents of the `prelude` file
+    File,
+    /// only the `prelude-str` string
+    Str,
+    /// the `prelude` file contents followed by the `prelude-str` string
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionPosition {
+    Above,
+    Below,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumberPosition {
+    /// before the heading text, as typst does
+    #[default]
+    Inline,
+    /// in the left margin, with the heading text aligned to the body
+    Margin,
+}
+
+/// The convention of heading ids used in the links of the book
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SlugStyle {
+    #[default]
+    Mdbook,
+    Github,
+    Pandoc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskStyle {
+    /// struck through
+    Strike,
+    /// in gray text
+    Muted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EnumDelimiter {
+    #[serde(rename = ".")]
+    Period,
+    #[serde(rename = ")")]
+    Paren,
 }
 
 impl Config {
-    pub fn prelude(&self, root: &Path) -> std::io::Result<String> {
-        if let Some(p) = &self.prelude_str {
-            return Ok(p.to_string());
+    pub fn prelude(&self, root: &Path, book: &BookConfig) -> std::io::Result<String> {
+        let file = || -> std::io::Result<Option<String>> {
+            let Some(path) = &self.prelude else {
+                return Ok(None);
+            };
+            match std::fs::read_to_string(root.join(path)) {
+                Ok(p) => Ok(Some(p)),
+                Err(e) if self.prelude_fallback => {
+                    eprintln!(
+                        "Warning: could not read the prelude {path:?} ({e}), using the default"
+                    );
+                    Ok(Some(self.default_prelude(book, true)))
+                }
+                Err(e) => Err(e),
+            }
+        };
+        // without a mode, the string takes precedence over the file
+        let prelude = match self.prelude_mode {
+            None => match &self.prelude_str {
+                Some(p) => Some(p.to_string()),
+                None => file()?,
+            },
+            Some(PreludeMode::File) => file()?,
+            Some(PreludeMode::Str) => self.prelude_str.clone(),
+            Some(PreludeMode::Both) => match (file()?, &self.prelude_str) {
+                (Some(f), Some(p)) => Some(format!("{f}\n{p}")),
+                (f, p) => f.or(p.clone()),
+            },
+        };
+        if let Some(p) = prelude {
+            return Ok(p);
         }
-        if let Some(p) = &self.prelude {
-            return std::fs::read_to_string(root.join(p));
+        // default style if none is given, the numbering of the contents
+        // starts after the front matter
+        Ok(self.default_prelude(book, self.front_matter.is_none()))
+    }
+
+    /// If neither a `prelude` nor a `prelude-str` is used for the prelude
+    pub fn uses_default_prelude(&self) -> bool {
+        match self.prelude_mode {
+            Some(PreludeMode::File) => self.prelude.is_none(),
+            Some(PreludeMode::Str) => self.prelude_str.is_none(),
+            _ => self.prelude.is_none() && self.prelude_str.is_none(),
         }
-        // default style if none is given
-        Ok(
+    }
+
+    /// Page numbering of the book contents in the default prelude, it
+    /// comes after the outline and the front matter
+    pub fn contents_numbering(&self) -> String {
+        // the total only counts the pages of the book contents
+        let footer = if self.page_x_of_y {
+            "#set page(footer: context align(center)[Page #counter(page).display() of #counter(page).final().first()])\n"
+        } else {
+            ""
+        };
+        if self.reset_page_numbering.unwrap_or(true) {
+            format!("#counter(page).update(1)\n#set page(numbering: \"1\")\n{footer}")
+        } else {
+            footer.to_string()
+        }
+    }
+
+    /// If the configured typst version has the features of `major.minor`;
+    /// without a version the latest one is assumed
+    pub fn typst_at_least(&self, major: u32, minor: u32) -> bool {
+        let Some(version) = &self.typst_version else {
+            return true;
+        };
+        let mut parts = version
+            .trim_start_matches('v')
+            .split('.')
+            .map(|p| p.parse::<u32>().unwrap_or_default());
+        let version = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        );
+        version >= (major, minor)
+    }
+
+    fn default_prelude(&self, book: &BookConfig, contents_numbering: bool) -> String {
+        let show_title_page = self.title_page.unwrap_or(true);
+        // without the reset, all the pages are numbered in one sequence
+        let front_numbering = if self.reset_page_numbering.unwrap_or(true) {
+            let numbering = self.front_matter_numbering.as_deref().unwrap_or("i");
+            format!("#set page(numbering: {numbering:?})\n#counter(page).update(1)\n")
+        } else {
+            "#set page(numbering: \"1\")\n".to_string()
+        };
+        // a book without a title has no title page
+        let title_page = match &book.title {
+            Some(title) if show_title_page => format!(
+                "#page(numbering: none, align(center + horizon)[#text(size: 24pt, {})\n\n{}])\n",
+                crate::typst_string(title),
+                book.authors
+                    .iter()
+                    .map(|a| format!("#text(size: 14pt, {})", crate::typst_string(a)))
+                    .collect::<Vec<String>>()
+                    .join(" \\\n")
+            ),
+            _ => String::new(),
+        };
+        let contents = if contents_numbering {
+            self.contents_numbering()
+        } else {
+            String::new()
+        };
+        let paper = self.paper.as_deref().unwrap_or("us-letter");
+        // typst uses its own font without one
+        let font = self
+            .font
+            .as_ref()
+            .map(|f| format!("font: {f:?}, "))
+            .unwrap_or_default();
+        let font_size = self.font_size.as_deref().unwrap_or("11pt");
+        if !is_known_paper(paper) {
+            eprintln!("Warning: {paper:?} may not be a typst paper size");
+        }
+        let code_width = self.code_block_width.as_deref().unwrap_or("100%");
+        // a plain number is in the units of the font size
+        let leading = match self.line_spacing.as_deref() {
+            Some(s) if s.parse::<f64>().is_ok() => format!("{s}em"),
+            Some(s) => s.to_string(),
+            None => ".8em".to_string(),
+        };
+        // optional rules, only emitted when configured
+        let mut rules = String::new();
+        if show_title_page || self.tagged_pdf {
+            // the pdf metadata, also needed by the readers of tagged pdfs
+            let mut fields = Vec::new();
+            if let Some(title) = &book.title {
+                fields.push(format!("title: {}", crate::typst_string(title)));
+            }
+            if !book.authors.is_empty() {
+                let authors: Vec<String> = book
+                    .authors
+                    .iter()
+                    .map(|a| crate::typst_string(a))
+                    .collect();
+                // a trailing comma keeps a single author an array
+                fields.push(format!("author: ({},)", authors.join(", ")));
+            }
+            if !fields.is_empty() {
+                rules.push_str(&format!("#set document({})\n", fields.join(", ")));
+            }
+        }
+        if self.tagged_pdf {
+            // typst tags the headings, figures and tables itself, the
+            // readers also need the language of the document
+            let lang = book.language.as_deref().unwrap_or("en");
+            rules.push_str(&format!("#set text(lang: {lang:?})\n"));
+        }
+        if let Some(pos) = self.caption_position {
+            let pos = match pos {
+                CaptionPosition::Above => "top",
+                CaptionPosition::Below => "bottom",
+            };
+            rules.push_str(&format!("#set figure.caption(position: {pos})\n"));
+        }
+        if self.heading_number_position == Some(NumberPosition::Margin) {
+            rules.push_str("#show heading: it => if it.numbering == none { it } else { block(place(dx: -3em, counter(heading).display(it.numbering)) + it.body) }\n");
+        }
+        // typst hyphenates justified text by default
+        if let Some(hyphenate) = self.hyphenate {
+            rules.push_str(&format!("#set text(hyphenate: {hyphenate})\n"));
+        }
+        if let Some(color) = &self.link_color {
+            rules.push_str(&format!("#show link: set text(fill: {color})\n"));
+        }
+        if self.link_underline == Some(true) {
+            rules.push_str("#show link: underline\n");
+        }
+        if let Some(supplement) = &self.figure_supplement {
+            rules.push_str(&format!(
+                "#show figure.where(kind: image): set figure(supplement: [{supplement}])\n"
+            ));
+        }
+        if let Some(size) = &self.table_font_size {
+            rules.push_str(&format!("#show table: set text(size: {size})\n"));
+        }
+        if self.code_lang_badge {
+            rules.push_str("#show raw.where(block: true): it => if it.lang == none { it } else { block(width: 100%, place(top + right, dx: -4pt, dy: 4pt, box(fill: luma(200), inset: 3pt, radius: 2pt, text(size: 7pt, upper(it.lang)))) + it) }\n");
+        }
+        if self.code_wrap {
+            rules.push_str(
+                "#show raw.where(block: true): set par(justify: false, linebreaks: \"simple\")\n",
+            );
+        }
+        format!(
             r#"
 #set heading(numbering: "1.", depth: 3)
-#set page(paper: "us-letter")
-#set text(size: 11pt)
-#set par(spacing:2em, leading: .8em, justify: true)
-#show raw: set block(fill: luma(230), inset: 8pt, radius: 4pt, width: 100%)
+#set page(paper: {paper:?})
+#set text({font}size: {font_size})
+#set par(spacing:2em, leading: {leading}, justify: true)
+#show raw: set block(fill: luma(230), inset: 8pt, radius: 4pt, width: {code_width})
 
 #let unum_chap(contents) = align(center, text(size:16pt, contents))
 #let bookpart(contents) = block(fill:luma(200), inset: 8pt, width: 100%, align(center, text(size:16pt, contents)))
 
 #show quote: set block(fill: luma(230), inset: 8pt, radius: 4pt, width: 100%)
-#let htmlblock(cat, contents) = block(fill: yellow.lighten(50%), inset: 8pt, radius: 4pt, width: 100%, contents)
-
-#set page(numbering: "i")
-#counter(page).update(1)
-#outline(depth: 2, indent: 2em)
+#let alertcolors = (note: blue, tip: green, important: purple, warning: orange, caution: red)
+#let htmlblock(cat, contents) = block(fill: alertcolors.at(cat, default: yellow).lighten(if cat in alertcolors {{ 80% }} else {{ 50% }}), inset: 8pt, radius: 4pt, width: 100%, contents)
+{rules}
+{title_page}{front_numbering}#outline(depth: 2, indent: 2em)
 #pagebreak()
-#counter(page).update(1)
-#set page(numbering: "1")
-"#.to_string(),
+{contents}"#
         )
     }
 }
+
+/// If typst has the paper size, only the usual ones are checked: the
+/// ISO A, B and C series and the US sizes, with the names typst gives them
+fn is_known_paper(paper: &str) -> bool {
+    let series = |prefix: &str, sizes: std::ops::RangeInclusive<u8>| {
+        paper
+            .strip_prefix(prefix)
+            .and_then(|n| n.parse::<u8>().ok())
+            .is_some_and(|n| sizes.contains(&n))
+    };
+    series("a", 0..=11)
+        || series("iso-b", 1..=8)
+        || series("iso-c", 3..=8)
+        || matches!(
+            paper,
+            "us-letter" | "us-legal" | "us-tabloid" | "us-executive" | "us-statement"
+        )
+}
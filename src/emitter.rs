@@ -0,0 +1,259 @@
+/// Typst markup written for the book and markdown elements
+///
+/// All the methods have the default markup, so an emitter only needs to
+/// override the elements it renders differently; it can wrap
+/// [`DefaultEmitter`] to reuse the default markup around its own.
+pub trait TypstEmitter {
+    /// Title of a part, `label` is the label to attach to it
+    ///
+    /// The title is in a figure of its own kind, so that `@label` refers
+    /// to it as `Part 1`; a `#bookpart` block can't be referenced.
+    fn part_title(&self, title: &str, label: &str) -> String {
+        format!(
+            "\n#pagebreak(weak: true)\n#set page(header:[#h(1fr) {title}])\n#figure(kind: \"part\", supplement: [Part], outlined: false, bookpart()[{title}]) <{label}>\n"
+        )
+    }
+
+    /// Title of a chapter, `level` is `None` for unnumbered chapters and
+    /// `label` is the label for the links to the chapter
+    fn chapter_title(&self, name: &str, level: Option<usize>, label: Option<&str>) -> String {
+        let label = label.map(|l| format!(" <{l}>")).unwrap_or_default();
+        match level {
+            Some(level) => format!("\n#heading(level:{level})[{name}]{label}\n"),
+            None => format!("\n#unum_chap()[{name}]{label}\n"),
+        }
+    }
+
+    /// Start of a heading inside a chapter; headings of unnumbered
+    /// chapters are not `numbered` and are made bold instead
+    fn heading_start(&self, level: usize, numbered: bool) -> String {
+        if numbered {
+            format!("\n{} ", "=".repeat(level))
+        } else {
+            "\n*".to_string()
+        }
+    }
+
+    fn heading_end(&self, numbered: bool) -> String {
+        if numbered {
+            "\n".to_string()
+        } else {
+            ":*\\\n".to_string()
+        }
+    }
+
+    // it makes four empty line, but overkill better than incorrect
+    fn paragraph_start(&self) -> String {
+        "\n\n\n".to_string()
+    }
+
+    fn paragraph_end(&self) -> String {
+        "\n\n".to_string()
+    }
+
+    /// Inline code, as `#raw` when the code has backticks that would end
+    /// the markup early
+    fn inline_code(&self, code: &str) -> String {
+        if code.contains('`') {
+            format!("#raw({})", crate::typst_string(code))
+        } else {
+            format!("`{code}`")
+        }
+    }
+
+    /// Checkbox of a task list item, followed by the item text
+    fn task_marker(&self, checked: bool) -> String {
+        if checked {
+            "☒ ".to_string()
+        } else {
+            "☐ ".to_string()
+        }
+    }
+
+    /// A code block with its text; the fence is longer than any run of
+    /// backticks in the code so it can't be closed early
+    fn code_block(&self, info: &CodeInfo, code: &str) -> String {
+        let lang = &info.lang;
+        let fence = "`".repeat(fence_len(code));
+        let label = info
+            .id
+            .as_ref()
+            .map(|id| format!("<{id}>"))
+            .unwrap_or_default();
+        if info.number_lines() {
+            format!(
+                "\n#[\n#show raw.line: it => box(width: 2em, align(right, text(fill: gray)[#it.number])) + h(1em) + it.body\n{fence}{lang}\n{code}{fence}{label}\n]\n"
+            )
+        } else {
+            format!("\n{fence}{lang}\n{code}{fence}{label}\n")
+        }
+    }
+
+    /// A console code block with its prompt lines (`$ ` and `> `) in bold
+    fn console_block(&self, block: &str) -> String {
+        format!(
+            "\n#[\n#show raw.line: it => if it.text.starts-with(\"$ \") or it.text.starts-with(\"> \") {{ strong(it.body) }} else {{ it }}{block}]\n"
+        )
+    }
+
+    /// A code block or table in a figure with a caption, `kind` is the
+    /// typst figure kind like `raw` or `table`
+    fn figure(&self, kind: &str, body: &str, caption: &str) -> String {
+        format!("\n#figure(kind: {kind}, caption: [{caption}])[{body}]\n")
+    }
+
+    /// An image with its title or alt text as the caption; `path` is
+    /// already a typst string, `width` a typst length, `alt` the plain alt
+    /// text and `caption` typst markup, the figure has no caption when it
+    /// is empty
+    fn image(&self, path: &str, width: Option<&str>, alt: Option<&str>, caption: &str) -> String {
+        let width = width.map(|w| format!(", width: {w}")).unwrap_or_default();
+        let alt = alt
+            .map(|a| format!(", alt: {}", crate::typst_string(a)))
+            .unwrap_or_default();
+        if caption.trim().is_empty() {
+            format!("\n#figure(image({path}{width}{alt}))\n")
+        } else {
+            format!("\n#figure(image({path}{width}{alt}), caption: [{caption}])\n")
+        }
+    }
+
+    /// A table from its column alignments (`auto`, `left`, `center` or
+    /// `right`), header cells and body cells
+    fn table(&self, aligns: &[&str], headers: &[String], cells: &[String]) -> String {
+        let mut rows = Vec::new();
+        if !headers.is_empty() {
+            let headers = headers
+                .iter()
+                .map(|h| format!("[*{h}*]"))
+                .collect::<Vec<String>>()
+                .join(", ");
+            rows.push(format!("table.header({headers})"));
+        }
+        // a table with only the header has no body line
+        if !cells.is_empty() {
+            let cells = cells
+                .iter()
+                .map(|h| format!("[{h}]"))
+                .collect::<Vec<String>>()
+                .join(", ");
+            rows.push(cells);
+        }
+        // the alignments are only written when some column has one
+        let align = if aligns.iter().all(|a| *a == "auto") {
+            String::new()
+        } else {
+            format!("\n  align: ({}),", aligns.join(", "))
+        };
+        format!(
+            "
+#table(
+  columns: {},{align}
+  {}
+)
+
+",
+            aligns.len(),
+            rows.join(",\n  "),
+        )
+    }
+
+    /// A thematic break, `---` in markdown
+    fn rule(&self) -> String {
+        "\n#line(length: 100%)\n".to_string()
+    }
+
+    /// A block quote from its rendered contents, with the attribution
+    /// from its last line when there is one
+    fn quote(&self, body: &str, attribution: Option<&str>) -> String {
+        let attribution = attribution
+            .map(|a| format!(", attribution: [{a}]"))
+            .unwrap_or_default();
+        format!("\n#quote(block: true{attribution})[\n{}\n]\n", body.trim())
+    }
+
+    /// Start of a github alert (`> [!NOTE]`), `kind` is in lowercase
+    fn alert_start(&self, kind: &str) -> String {
+        format!("\n#htmlblock(\"{kind}\")[\n")
+    }
+
+    fn alert_end(&self) -> String {
+        "]\n".to_string()
+    }
+
+    /// A footnote from its already rendered contents, `label` lets the
+    /// later references to the same note point to it
+    fn footnote(&self, note: &str, label: &str) -> String {
+        if note.contains('\n') {
+            // block contents need to start on their own line
+            format!("#footnote[\n{note}\n] <{label}>")
+        } else {
+            format!("#footnote[{note}] <{label}>")
+        }
+    }
+
+    /// Another reference to an already shown footnote
+    fn footnote_ref(&self, label: &str) -> String {
+        format!("#footnote(<{label}>)")
+    }
+}
+
+/// Length of the raw block fence for `code`, six backticks unless the
+/// code has a run of as many
+pub fn fence_len(code: &str) -> usize {
+    let longest = code
+        .split(|c| c != '`')
+        .map(|run| run.len())
+        .max()
+        .unwrap_or_default();
+    longest.max(5) + 1
+}
+
+/// The emitter with the default markup for everything
+pub struct DefaultEmitter;
+
+impl TypstEmitter for DefaultEmitter {}
+
+/// The info string of a fenced code block, like `rust {#lst:x .numberLines}`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CodeInfo {
+    /// language, the first word; empty when not given
+    pub lang: String,
+    /// id from `#id` in the attributes
+    pub id: Option<String>,
+    /// classes from `.class` in the attributes
+    pub classes: Vec<String>,
+}
+
+impl CodeInfo {
+    pub fn parse(info: &str) -> Self {
+        let (words, attrs) = match info.split_once('{') {
+            Some((w, a)) => (w, a.trim_end().trim_end_matches('}')),
+            None => (info, ""),
+        };
+        // mdbook uses comma separated flags like `rust,ignore`
+        let lang = words
+            .split([',', ' ', '\t'])
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let mut code = CodeInfo {
+            lang,
+            ..Default::default()
+        };
+        for attr in attrs.split_whitespace() {
+            if let Some(id) = attr.strip_prefix('#') {
+                code.id = Some(id.to_string());
+            } else if let Some(class) = attr.strip_prefix('.') {
+                code.classes.push(class.to_string());
+            }
+        }
+        code
+    }
+
+    pub fn number_lines(&self) -> bool {
+        self.classes
+            .iter()
+            .any(|c| c == "numberLines" || c == "number-lines")
+    }
+}
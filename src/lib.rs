@@ -0,0 +1,2205 @@
+use anyhow::Context;
+use mdbook_renderer::book::{BookItem, Chapter};
+use mdbook_renderer::RenderContext;
+use pulldown_cmark::{
+    Alignment, BlockQuoteKind, CodeBlockKind, Event, HeadingLevel, LinkType, Options, Parser, Tag,
+    TagEnd,
+};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub mod config;
+pub mod emitter;
+
+use config::{Config, EnumDelimiter, SlugStyle, TaskStyle};
+use emitter::{CodeInfo, DefaultEmitter, TypstEmitter};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+
+/// Tables with more rows are allowed to break with `table-avoid-break`,
+/// they would not fit on a page otherwise
+const MAX_UNBROKEN_ROWS: usize = 30;
+
+/// Book wide settings needed while rendering the chapters
+struct Env<'a> {
+    cfg: &'a Config,
+    emitter: &'a dyn TypstEmitter,
+    src_dir: PathBuf,
+    dest_dir: PathBuf,
+    /// the labels given so far, with the chapter or part they are in
+    labels: RefCell<HashMap<String, String>>,
+    /// the labels given more than once
+    repeated_labels: RefCell<Vec<String>>,
+    /// labels of the footnotes already shown, the later references refer to them
+    shown_notes: RefCell<HashSet<String>>,
+    /// after an appendix part title, in the block of its numbering
+    in_appendix: Cell<bool>,
+}
+
+impl Env<'_> {
+    /// Keep track of a label given in `place`, a repeated label is
+    /// ambiguous for typst, so it is warned about
+    fn add_label(&self, label: &str, place: &str) {
+        let mut labels = self.labels.borrow_mut();
+        match labels.get(label) {
+            Some(other) => {
+                eprintln!("Warning: the label <{label}> of {place:?} is also used in {other:?}");
+                self.repeated_labels.borrow_mut().push(label.to_string());
+            }
+            None => {
+                labels.insert(label.to_string(), place.to_string());
+            }
+        }
+    }
+}
+
+/// Options for [`render_markdown_to_string`], the settings of the book
+/// and the place of the markdown in it
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    pub config: Config,
+    /// source directory of the book, the images are relative to it
+    pub src_dir: PathBuf,
+    /// build directory, for the converted images and bundled assets
+    pub dest_dir: PathBuf,
+    /// level of the chapter, its headings come after it
+    pub level: usize,
+    /// if the headings are numbered
+    pub numbered: bool,
+    /// chapter name, used in the labels when there is no `path`
+    pub name: String,
+    /// path of the chapter relative to `src_dir`
+    pub path: Option<PathBuf>,
+}
+
+/// Render the markdown of a chapter to typst, without its title
+pub fn render_markdown_to_string(md: &str, opts: RenderOptions) -> String {
+    render_markdown_with(md, opts, &DefaultEmitter)
+}
+
+/// Render the markdown of a chapter to typst with the markup of `emitter`
+pub fn render_markdown_with(md: &str, opts: RenderOptions, emitter: &dyn TypstEmitter) -> String {
+    let env = Env {
+        cfg: &opts.config,
+        emitter,
+        src_dir: opts.src_dir.clone(),
+        dest_dir: opts.dest_dir.clone(),
+        labels: Default::default(),
+        repeated_labels: Default::default(),
+        shown_notes: Default::default(),
+        in_appendix: Default::default(),
+    };
+    let info = ChapInfo {
+        level: opts.level,
+        path: opts.path.as_deref(),
+        name: &opts.name,
+        number: opts.numbered,
+    };
+    let mut buf = Vec::new();
+    // markdown written on windows can have `\r\n` line endings
+    let md = md.replace("\r\n", "\n");
+    write_markdown(&mut buf, &env, md, &info).expect("writing to a Vec can't fail");
+    String::from_utf8_lossy(&buf).to_string()
+}
+
+/// The typst of a book: the main file, and the files it includes with
+/// `split-by-chapter`
+#[derive(Debug, Clone, Default)]
+pub struct TypstBook {
+    pub main: String,
+    /// the included files relative to the main file, the prelude and the
+    /// chapters in the order of the book
+    pub files: Vec<(PathBuf, String)>,
+}
+
+/// File with the prelude the split chapters import their functions from
+const SPLIT_PRELUDE: &str = "prelude.typ";
+
+/// Render the whole book to typst: the prelude, the chapters and the
+/// front and back matter
+pub fn render_book(ctx: &RenderContext, cfg: &Config) -> anyhow::Result<TypstBook> {
+    render_book_with(ctx, cfg, &DefaultEmitter)
+}
+
+/// Render the whole book to typst with the markup of `emitter`
+pub fn render_book_with(
+    ctx: &RenderContext,
+    cfg: &Config,
+    emitter: &dyn TypstEmitter,
+) -> anyhow::Result<TypstBook> {
+    let mut writer = Vec::new();
+    let mut files = Vec::new();
+    let prelude = cfg
+        .prelude(&ctx.root, &ctx.config.book)
+        .with_context(|| format!("failed to read the prelude {:?}", cfg.prelude))?;
+    writeln!(writer, "{prelude}")?;
+    if cfg.split_by_chapter {
+        // included files don't see the functions of the main file
+        files.push((PathBuf::from(SPLIT_PRELUDE), prelude));
+    }
+    if cfg.enum_delimiter == Some(EnumDelimiter::Paren) {
+        // the numbers are written in the items, this only changes the delimiter
+        writeln!(writer, "#set enum(numbering: \"1)\")")?;
+    }
+    if let Some(offsets) = &cfg.counter_offsets {
+        // sorted so the output doesn't change between runs
+        let mut offsets: Vec<_> = offsets.iter().collect();
+        offsets.sort();
+        for (name, value) in offsets {
+            let counter = match name.as_str() {
+                "heading" | "page" | "footnote" => name.to_string(),
+                "image" | "table" | "raw" => format!("figure.where(kind: {name})"),
+                _ => {
+                    eprintln!("Warning: unknown counter {name:?} in counter-offsets");
+                    continue;
+                }
+            };
+            writeln!(writer, "#counter({counter}).update({value})")?;
+        }
+    }
+    if let Some(front) = &cfg.front_matter {
+        let front = ctx.root.join(front);
+        let front = std::fs::read_to_string(&front)
+            .with_context(|| format!("failed to read the front matter {front:?}"))?;
+        writeln!(writer, "{front}")?;
+        if cfg.uses_default_prelude() {
+            // the front matter pages are numbered like the outline, and
+            // the contents start on their own page
+            writeln!(writer, "#pagebreak(weak: true)")?;
+            write!(writer, "{}", cfg.contents_numbering())?;
+        }
+    }
+
+    let env = Env {
+        cfg,
+        emitter,
+        src_dir: ctx.root.join(&ctx.config.book.src),
+        dest_dir: ctx.destination.clone(),
+        labels: Default::default(),
+        repeated_labels: Default::default(),
+        shown_notes: Default::default(),
+        in_appendix: Default::default(),
+    };
+    for section in ctx.book.items.clone() {
+        match section {
+            // the sub chapters are in the file of their chapter
+            BookItem::Chapter(chap) if cfg.split_by_chapter => {
+                // the prelude is the first file, so the chapters count from 1
+                let name = chapter_filename(cfg, files.len(), &chap.name);
+                if files.iter().any(|(n, _)| *n == name) {
+                    anyhow::bail!(
+                        "chapter-filename-template gives the same file {name:?} to two chapters"
+                    );
+                }
+                // only the functions are imported, the rules come from the
+                // main file
+                let prelude =
+                    "../".repeat(name.components().count().saturating_sub(1)) + SPLIT_PRELUDE;
+                let mut file = Vec::new();
+                writeln!(file, "#import {}: *", typst_string(&prelude))?;
+                write_bookitem(&mut file, &env, BookItem::Chapter(chap), 0)?;
+                writeln!(
+                    writer,
+                    "\n#include {}",
+                    typst_string(&name.to_string_lossy())
+                )?;
+                files.push((name, String::from_utf8(file)?));
+            }
+            section => write_bookitem(&mut writer, &env, section, 0)?,
+        }
+    }
+    if env.in_appendix.get() {
+        writeln!(writer, "]")?;
+    }
+    if let Some(changelog) = &cfg.changelog {
+        let path = ctx.root.join(changelog);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read the changelog {path:?}"))?;
+        // chapter paths are relative to the source directory
+        let path = path.strip_prefix(&env.src_dir).unwrap_or(changelog);
+        let chapter = Chapter::new("Changelog", content, path, Vec::new());
+        writeln!(writer, "\n#pagebreak(weak: true)")?;
+        write_chapter(&mut writer, &env, chapter, 0, false)?;
+    }
+    if let Some(back) = &cfg.back_matter {
+        let back = ctx.root.join(back);
+        let back = std::fs::read_to_string(&back)
+            .with_context(|| format!("failed to read the back matter {back:?}"))?;
+        writeln!(writer, "{back}")?;
+    }
+
+    let repeated = env.repeated_labels.take();
+    if env.cfg.strict_labels && !repeated.is_empty() {
+        anyhow::bail!("labels used more than once: {}", repeated.join(", "));
+    }
+
+    let mut book = TypstBook {
+        main: String::from_utf8(writer)?,
+        files,
+    };
+    if cfg.compact_output {
+        book.main = compact_typst(&book.main);
+        for (_, file) in &mut book.files {
+            *file = compact_typst(file);
+        }
+    }
+    Ok(book)
+}
+
+/// File of the `index`th top level chapter from the
+/// `chapter-filename-template`, `{number}` is the index with two digits
+/// and `{slug}` is made from the chapter name
+fn chapter_filename(cfg: &Config, index: usize, name: &str) -> PathBuf {
+    let template = cfg
+        .chapter_filename_template
+        .as_deref()
+        .unwrap_or("{number}-{slug}.typ");
+    template
+        .replace("{number}", &format!("{index:02}"))
+        .replace("{slug}", &heading_slug(name, SlugStyle::Mdbook))
+        .into()
+}
+
+/// Collapse the runs of blank lines outside of raw blocks into one
+///
+/// Typst treats any number of blank lines as a single paragraph break,
+/// so this doesn't change the rendered document.
+fn compact_typst(typ: &str) -> String {
+    let mut out = String::with_capacity(typ.len());
+    let mut fence: Option<usize> = None;
+    let mut blank = false;
+    for line in typ.lines() {
+        let ticks = line.len() - line.trim_start_matches('`').len();
+        match fence {
+            // the closing fence may have a label after it
+            Some(n) if ticks >= n => fence = None,
+            Some(_) => (),
+            None if ticks >= 3 => fence = Some(ticks),
+            None if line.trim().is_empty() => {
+                if !blank {
+                    out.push('\n');
+                }
+                blank = true;
+                continue;
+            }
+            None => (),
+        }
+        blank = false;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+fn write_bookitem(
+    writer: &mut impl Write,
+    env: &Env,
+    item: BookItem,
+    level: usize,
+) -> std::io::Result<()> {
+    match item {
+        // weak breaks so a separator before a part title doesn't leave a blank page
+        BookItem::Separator => {
+            writeln!(writer, "\n#pagebreak(weak: true)")?;
+            if env.cfg.separator_marker {
+                // invisible, for the preludes that group the outline
+                writeln!(writer, "#metadata(\"separator\") <separator>")?;
+            }
+            Ok(())
+        }
+        BookItem::PartTitle(title) => {
+            // the same ids as the headings, from the text without its markup
+            let id = heading_slug(&plain_text(&title), env.cfg.slug_style.unwrap_or_default());
+            let label = format!("part:{id}");
+            env.add_label(&label, &title);
+            if env.in_appendix.get() {
+                // the parts after the appendices have the prelude numbering
+                writeln!(writer, "]")?;
+            }
+            let typ = env
+                .emitter
+                .part_title(&inline_markdown(env, &title), &label);
+            write!(writer, "{typ}")?;
+            let appendix = title.to_lowercase().starts_with("appendi");
+            if appendix {
+                // appendices are lettered, starting again from A, in a
+                // block that keeps the numbering to them
+                let numbering = env.cfg.appendix_numbering.as_deref().unwrap_or("A.1");
+                writeln!(writer, "#[")?;
+                writeln!(
+                    writer,
+                    "#set heading(numbering: {})",
+                    typst_string(numbering)
+                )?;
+                writeln!(writer, "#counter(heading).update(0)")?;
+            } else if env.cfg.restart_numbering_per_part {
+                writeln!(writer, "#counter(heading).update(0)")?;
+            }
+            env.in_appendix.set(appendix);
+            Ok(())
+        }
+        BookItem::Chapter(chap) => {
+            if let Some(num) = chap.number.clone() {
+                write_chapter(writer, env, chap, num.len(), true)
+            } else {
+                write_chapter(writer, env, chap, level, false)
+            }
+        }
+    }
+}
+fn write_chapter(
+    writer: &mut impl Write,
+    env: &Env,
+    chapter: Chapter,
+    mut level: usize,
+    number: bool,
+) -> std::io::Result<()> {
+    // books written on windows can have `\r\n` line endings
+    let content = chapter.content.replace("\r\n", "\n");
+    let (meta, contents) = front_matter(&content);
+    // a chapter on a different paper is its own pages
+    let paper = meta.get("typst_paper");
+    if let Some(paper) = paper {
+        writeln!(writer, "\n#page(paper: {})[", typst_string(paper))?;
+    }
+    // chapters without a file, like the drafts, can't be linked to
+    let label = chapter.path.as_deref().map(chapter_label);
+    if let Some(label) = &label {
+        env.add_label(label, &chapter.name);
+    }
+    let title = env.emitter.chapter_title(
+        &escape_typst(chapter.name.as_str().into()),
+        number.then_some(level),
+        label.as_deref(),
+    );
+    write!(writer, "{title}")?;
+
+    // if the chapter content has multiple top level titles
+    let top_titles = top_titles(contents);
+    // only a `# ` title on the first line that isn't blank is the chapter
+    // title, a chapter can start with anything else
+    let body = contents.trim_start();
+    let after_title = body
+        .starts_with("# ")
+        .then(|| body.split_once('\n').map_or("", |(_, rest)| rest));
+    let contents = match after_title {
+        Some(rest) if top_titles == 1 => {
+            // top level unnumbered chapters are already at 0
+            level = level.saturating_sub(1);
+            rest
+        }
+        // first title is the chapter title, keeping the level demotes the rest
+        Some(rest) if env.cfg.demote_extra_h1 && top_titles > 1 => rest,
+        _ => contents,
+    }
+    .to_string();
+    let info = ChapInfo {
+        level,
+        path: chapter.path.as_deref(),
+        name: &chapter.name,
+        number,
+    };
+    write_markdown(writer, env, contents, &info)?;
+
+    for item in chapter.sub_items {
+        write_bookitem(writer, env, item, level + 1)?;
+    }
+    if paper.is_some() {
+        writeln!(writer, "]")?;
+    }
+    writeln!(writer)
+}
+
+/// Number of the `# ` titles in the markdown, the comments like `# x`
+/// in the fenced code blocks are not titles
+fn top_titles(md: &str) -> usize {
+    let mut fence: Option<&str> = None;
+    let mut count = 0;
+    for line in md.lines() {
+        let trimmed = line.trim_start();
+        match fence {
+            // the closing fence is at least as long as the opening one
+            Some(f)
+                if trimmed.starts_with(f)
+                    && trimmed.trim_start_matches(&f[..1]).trim().is_empty() =>
+            {
+                fence = None
+            }
+            Some(_) => (),
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                let len = trimmed.len() - trimmed.trim_start_matches(&trimmed[..1]).len();
+                fence = Some(&trimmed[..len]);
+            }
+            None if line.starts_with("# ") => count += 1,
+            None => (),
+        }
+    }
+    count
+}
+
+/// The keys of the chapter front matter
+const FRONT_MATTER_KEYS: &[&str] = &["typst_paper"];
+
+/// Split the `---` delimited front matter at the start of a chapter
+/// from its contents
+///
+/// A chapter can also start with a `---` rule, so the block is only the
+/// front matter when all its lines are `key: value` with the known keys.
+fn front_matter(content: &str) -> (HashMap<String, String>, &str) {
+    let mut meta = HashMap::new();
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return (meta, content);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (meta, content);
+    };
+    for line in rest[..end].lines().filter(|l| !l.trim().is_empty()) {
+        match line.split_once(':') {
+            Some((key, value)) if FRONT_MATTER_KEYS.contains(&key.trim()) => {
+                let value = value.trim().trim_matches(['"', '\'']);
+                meta.insert(key.trim().to_string(), value.to_string());
+            }
+            _ => return (HashMap::new(), content),
+        }
+    }
+    let body = &rest[end + 4..];
+    (meta, body.split_once('\n').map_or("", |(_, b)| b))
+}
+
+#[derive(Default)]
+struct MdTable {
+    aligns: Vec<&'static str>,
+    headers: Vec<String>,
+    on_cell: bool,
+    thiscell: String,
+    cells: Vec<String>,
+    /// closing markup of the strong/emphasis open in the current cell
+    styles: Vec<&'static str>,
+    /// caption from the `Table: ` paragraph after the table
+    caption: Option<String>,
+}
+
+impl MdTable {
+    /// Open strong/emphasis in the cell; nested ones use the function
+    /// form so the adjacent markers can't be misparsed by typst
+    fn open_style(&mut self, markup: &'static str, func: &'static str) {
+        if self.styles.is_empty() {
+            self.thiscell.push_str(markup);
+            self.styles.push(markup);
+        } else {
+            self.thiscell.push_str(func);
+            self.thiscell.push('[');
+            self.styles.push("]");
+        }
+    }
+
+    fn close_style(&mut self) {
+        if let Some(close) = self.styles.pop() {
+            self.thiscell.push_str(close);
+        }
+    }
+}
+
+/// The chapter the markdown being written belongs to
+#[derive(Clone, Copy)]
+struct ChapInfo<'a> {
+    level: usize,
+    path: Option<&'a Path>,
+    name: &'a str,
+    number: bool,
+}
+
+fn write_markdown(
+    writer: &mut impl Write,
+    env: &Env,
+    md: String,
+    chap: &ChapInfo,
+) -> std::io::Result<()> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_GFM);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    if env.cfg.math {
+        options.insert(Options::ENABLE_MATH);
+    }
+    if env.cfg.sub_superscript {
+        // `~~` stays the strikethrough; the parser leaves the scripts
+        // inside words like `H~2~O` as text, they are found in the text
+        options.insert(Options::ENABLE_SUBSCRIPT);
+    }
+    let parser = Parser::new_ext(&md, options);
+
+    // footnote definitions can come after their use, so they are
+    // collected first and rendered with their block contents at the
+    // first use of their label
+    let mut events = Vec::new();
+    let mut definitions: HashMap<String, Vec<Event>> = HashMap::new();
+    let mut definition: Option<(String, Vec<Event>)> = None;
+    for event in parser {
+        match event {
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                definition = Some((label.to_string(), Vec::new()));
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                if let Some((label, evts)) = definition.take() {
+                    definitions.insert(label, evts);
+                }
+            }
+            e => match &mut definition {
+                Some((_, evts)) => evts.push(e),
+                None => events.push(e),
+            },
+        }
+    }
+    let highlight = |evts| {
+        if env.cfg.pandoc_highlight {
+            highlight_spans(evts)
+        } else {
+            evts
+        }
+    };
+    let footnotes = definitions
+        .into_iter()
+        .map(|(label, evts)| (label, highlight(merge_text(drop_empty_paragraphs(evts)))))
+        .collect();
+    let events = merge_text(drop_empty_paragraphs(table_captions(find_alerts(events))));
+    let events = highlight(events);
+    write_events(writer, env, events, chap, &footnotes)
+}
+
+/// Move a `Table: caption` paragraph right after a table into the table
+///
+/// The caption paragraph is then the last thing in the table, so it is
+/// written as the caption with its inline markup.
+fn table_captions(events: Vec<Event>) -> Vec<Event> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut iter = events.into_iter().peekable();
+    while let Some(event) = iter.next() {
+        if event != Event::End(TagEnd::Table) {
+            out.push(event);
+            continue;
+        }
+        let mut para = Vec::new();
+        if matches!(iter.peek(), Some(Event::Start(Tag::Paragraph))) {
+            para.extend(iter.next());
+            match iter.peek() {
+                Some(Event::Text(t)) if t.starts_with("Table:") => {
+                    let caption = t["Table:".len()..].trim_start().to_string();
+                    iter.next();
+                    para.push(Event::Text(caption.into()));
+                    for e in iter.by_ref() {
+                        let end = e == Event::End(TagEnd::Paragraph);
+                        para.push(e);
+                        if end {
+                            break;
+                        }
+                    }
+                    out.append(&mut para);
+                }
+                _ => (),
+            }
+        }
+        out.push(event);
+        // a paragraph that isn't a caption stays after the table
+        out.extend(para);
+    }
+    out
+}
+
+/// Find the github alerts with a title, like `> [!NOTE] Title`
+///
+/// The parser only knows the alerts with the `[!KIND]` marker alone on
+/// its line, this turns the titled ones into alerts too, with the title
+/// as a bold paragraph at the start.
+fn find_alerts(events: Vec<Event>) -> Vec<Event> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut quotes = Vec::new();
+    let mut iter = events.into_iter().peekable();
+    while let Some(event) = iter.next() {
+        match event {
+            Event::Start(Tag::BlockQuote(None))
+                if matches!(iter.peek(), Some(Event::Start(Tag::Paragraph))) =>
+            {
+                let para = iter.next().unwrap_or(Event::Start(Tag::Paragraph));
+                // events of the first line, the title can have inline markup
+                let mut first = Vec::new();
+                while let Some(e) = iter.peek()
+                    && !matches!(e, Event::SoftBreak | Event::End(TagEnd::Paragraph))
+                {
+                    first.extend(iter.next());
+                }
+                let texts = first
+                    .iter()
+                    .position(|e| !matches!(e, Event::Text(_)))
+                    .unwrap_or(first.len());
+                let line: String = first[..texts]
+                    .iter()
+                    .map(|t| match t {
+                        Event::Text(t) => t.as_ref(),
+                        _ => "",
+                    })
+                    .collect();
+                if let Some((kind, title)) = parse_alert(&line) {
+                    quotes.push(Some(kind));
+                    out.push(Event::Start(Tag::BlockQuote(Some(kind))));
+                    out.extend([Event::Start(Tag::Paragraph), Event::Start(Tag::Strong)]);
+                    if !title.is_empty() {
+                        out.push(Event::Text(title.to_string().into()));
+                    }
+                    out.extend(first.drain(texts..));
+                    out.extend([Event::End(TagEnd::Strong), Event::End(TagEnd::Paragraph)]);
+                    if matches!(iter.peek(), Some(Event::SoftBreak)) {
+                        iter.next();
+                    }
+                    out.push(para);
+                } else {
+                    quotes.push(None);
+                    out.push(Event::Start(Tag::BlockQuote(None)));
+                    out.push(para);
+                    out.extend(first);
+                }
+            }
+            Event::Start(Tag::BlockQuote(kind)) => {
+                quotes.push(kind);
+                out.push(Event::Start(Tag::BlockQuote(kind)));
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                out.push(Event::End(TagEnd::BlockQuote(quotes.pop().flatten())));
+            }
+            e => out.push(e),
+        }
+    }
+    out
+}
+
+/// Kind and the start of the title from the first line of a titled
+/// alert: `[!NOTE] Title`
+fn parse_alert(line: &str) -> Option<(BlockQuoteKind, &str)> {
+    let (kind, title) = line.strip_prefix("[!")?.split_once(']')?;
+    let kind = match kind.to_uppercase().as_str() {
+        "NOTE" => BlockQuoteKind::Note,
+        "TIP" => BlockQuoteKind::Tip,
+        "IMPORTANT" => BlockQuoteKind::Important,
+        "WARNING" => BlockQuoteKind::Warning,
+        "CAUTION" => BlockQuoteKind::Caution,
+        _ => return None,
+    };
+    Some((kind, title.trim_start()))
+}
+
+/// Join the runs of text events, the parser splits the text at the
+/// characters that could have been markup like `~`
+fn merge_text(events: Vec<Event>) -> Vec<Event> {
+    let mut out: Vec<Event> = Vec::with_capacity(events.len());
+    for event in events {
+        match (out.last_mut(), event) {
+            (Some(Event::Text(prev)), Event::Text(t)) => {
+                *prev = format!("{prev}{t}").into();
+            }
+            (_, e) => out.push(e),
+        }
+    }
+    out
+}
+
+/// Inline html event of a `==` highlight mark paired by
+/// `highlight_spans`; the html from the parser always starts with `<`
+const HIGHLIGHT_MARK: &str = "==";
+
+/// Find the pandoc style `==text==` highlights, each one is replaced by
+/// the text between two `HIGHLIGHT_MARK` events
+///
+/// The marks are only paired in the same block, like a paragraph, a list
+/// item or a table cell, and a mark without its pair is left as text.
+/// The opening mark must start a word, and the closing mark must be
+/// preceded by a non whitespace character, so that `a == b` and `a==b`
+/// are left alone. The headings and code are never highlighted.
+fn highlight_spans(events: Vec<Event>) -> Vec<Event> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut run = Vec::new();
+    let mut literal = false;
+    for event in events {
+        let block = match &event {
+            Event::Start(tag) => !matches!(
+                tag,
+                Tag::Emphasis
+                    | Tag::Strong
+                    | Tag::Strikethrough
+                    | Tag::Superscript
+                    | Tag::Subscript
+                    | Tag::Link { .. }
+            ),
+            Event::End(tag) => !matches!(
+                tag,
+                TagEnd::Emphasis
+                    | TagEnd::Strong
+                    | TagEnd::Strikethrough
+                    | TagEnd::Superscript
+                    | TagEnd::Subscript
+                    | TagEnd::Link
+            ),
+            Event::Html(_) | Event::Rule => true,
+            _ => false,
+        };
+        if !block {
+            run.push(event);
+            continue;
+        }
+        if literal {
+            out.append(&mut run);
+        } else {
+            out.extend(pair_highlights(std::mem::take(&mut run)));
+        }
+        literal = matches!(
+            event,
+            Event::Start(Tag::Heading { .. } | Tag::CodeBlock(_) | Tag::MetadataBlock(_))
+        );
+        out.push(event);
+    }
+    out.extend(pair_highlights(run));
+    out
+}
+
+/// Pair the `==` marks in the text of a single block, see
+/// `highlight_spans`
+fn pair_highlights(run: Vec<Event>) -> Vec<Event> {
+    // event index, byte offset, and whether it can open or close a span
+    let mut marks = Vec::new();
+    for (i, event) in run.iter().enumerate() {
+        if let Event::Text(t) = event {
+            let mut from = 0;
+            while let Some(pos) = t[from..].find("==").map(|p| p + from) {
+                let (pre, post) = (&t[from..pos], &t[pos + 2..]);
+                let word_start = !t[..pos].ends_with(|c: char| c.is_alphanumeric() || c == '=');
+                let open = word_start && post.starts_with(|c: char| !c.is_whitespace() && c != '=');
+                marks.push((i, pos, open, !pre.ends_with(char::is_whitespace)));
+                from = pos + 2;
+            }
+        }
+    }
+    // pair them in order, an opening mark left without its pair is
+    // dropped and the later marks are paired again without it
+    let mut paired = Vec::new();
+    let mut unpaired = Vec::new();
+    loop {
+        paired.clear();
+        let mut open = None;
+        for (k, &(_, _, can_open, can_close)) in marks.iter().enumerate() {
+            if unpaired.contains(&k) {
+                continue;
+            }
+            match open {
+                Some(o) if can_close => {
+                    paired.extend([o, k]);
+                    open = None;
+                }
+                Some(_) => (),
+                None if can_open => open = Some(k),
+                None => (),
+            }
+        }
+        match open {
+            Some(o) => unpaired.push(o),
+            None => break,
+        }
+    }
+    if paired.is_empty() {
+        return run;
+    }
+    let mut out = Vec::with_capacity(run.len() + paired.len() * 2);
+    for (i, event) in run.into_iter().enumerate() {
+        let Event::Text(t) = &event else {
+            out.push(event);
+            continue;
+        };
+        let mut from = 0;
+        for &k in paired.iter().filter(|&&k| marks[k].0 == i) {
+            let pos = marks[k].1;
+            if pos > from {
+                out.push(Event::Text(t[from..pos].to_string().into()));
+            }
+            out.push(Event::InlineHtml(HIGHLIGHT_MARK.into()));
+            from = pos + 2;
+        }
+        if from == 0 {
+            out.push(event);
+        } else if from < t.len() {
+            out.push(Event::Text(t[from..].to_string().into()));
+        }
+    }
+    out
+}
+
+/// Remove the paragraphs that have nothing but whitespace in them, so
+/// they don't add stray spacing; a `&nbsp;` paragraph is kept, it is
+/// written on purpose to add space
+fn drop_empty_paragraphs(events: Vec<Event>) -> Vec<Event> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut para: Option<Vec<Event>> = None;
+    for event in events {
+        match (event, &mut para) {
+            (Event::Start(Tag::Paragraph), None) => para = Some(vec![Event::Start(Tag::Paragraph)]),
+            (Event::End(TagEnd::Paragraph), Some(p)) => {
+                let empty = p[1..].iter().all(|e| match e {
+                    Event::Text(t) => t.chars().all(|c| c.is_ascii_whitespace()),
+                    Event::SoftBreak | Event::HardBreak => true,
+                    _ => false,
+                });
+                if !empty {
+                    out.append(p);
+                    out.push(Event::End(TagEnd::Paragraph));
+                }
+                para = None;
+            }
+            (e, Some(p)) => p.push(e),
+            (e, None) => out.push(e),
+        }
+    }
+    out
+}
+
+fn write_events(
+    writer: &mut impl Write,
+    env: &Env,
+    events: Vec<Event>,
+    chap: &ChapInfo,
+    footnotes: &HashMap<String, Vec<Event>>,
+) -> std::io::Result<()> {
+    let ChapInfo {
+        level: chap_level,
+        path: chap_path,
+        name: chap_name,
+        number,
+    } = *chap;
+    let mut table: Option<MdTable> = None;
+    // the open lists, with the next number of the ordered ones
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut consec_par = false;
+    // right after the start of a list item, before its first block
+    let mut item_start = false;
+    // open list items, `true` if it is a styled completed task
+    let mut task_items: Vec<bool> = Vec::new();
+    let mut in_code = false;
+    let mut code_info = CodeInfo::default();
+    let mut code_text = String::new();
+    let passthrough = env.cfg.passthrough_lang.as_deref().unwrap_or("typst-raw");
+    let mut in_head = false;
+    // heading text and whether it had an explicit label, for the auto label
+    let mut head_text = String::new();
+    let mut head_label = false;
+    let mut head_ids: HashMap<String, usize> = HashMap::new();
+    let mut in_highlight = false;
+    let chap_id = chap_path.map_or(slug(chap_name), chapter_id);
+    let mut html_state = HtmlState {
+        tabs: env.cfg.render_tabs,
+        ..Default::default()
+    };
+    // the image path, title and plain alt text, and its caption built
+    // from the alt text events
+    let mut image: Option<(String, String, String)> = None;
+    let mut caption: Option<String> = None;
+    // the open html `<figure>`, its caption is built in `caption`
+    let mut figure: Option<HtmlFigure> = None;
+    let mut events = events.into_iter().peekable();
+    while let Some(event) = events.next() {
+        match event {
+            Event::Code(c) => {
+                if in_head {
+                    head_text.push_str(&c);
+                }
+                if let Some((_, _, alt)) = &mut image {
+                    alt.push_str(&c);
+                }
+                let code = env.emitter.inline_code(&c);
+                emit_inline(writer, &mut table, &mut caption, &code)?;
+            }
+            Event::Text(c) if in_code && code_info.lang == passthrough => code_text.push_str(&c),
+            Event::Text(c) if in_code => {
+                let hidden = env.cfg.hidden_line_prefix.as_deref();
+                for l in c.lines() {
+                    // like mdbook's hidden lines, left out of the printed code
+                    if hidden.is_some_and(|h| l.trim_start().starts_with(h)) {
+                        continue;
+                    }
+                    code_text.push_str(l);
+                    code_text.push('\n');
+                }
+            }
+            Event::Text(c) => {
+                if let Some((_, _, alt)) = &mut image {
+                    alt.push_str(&c);
+                }
+                let txt = if in_head {
+                    head_text.push_str(&c);
+                    head_label |= c.contains(" { #");
+                    let (txt, label) = maybe_label(&chap_id, c);
+                    if let Some(label) = label {
+                        env.add_label(&label, chap_name);
+                    }
+                    txt
+                } else {
+                    let mut txt = expand_tabs(escape_typst(c), env.cfg.tab_width.as_deref());
+                    if env.cfg.sub_superscript {
+                        txt = script_marks(&script_marks(&txt, "^", "#super"), "\\~", "#sub");
+                    }
+                    txt
+                };
+                emit_inline(writer, &mut table, &mut caption, &txt)?;
+            }
+            Event::Html(html) if figure.is_some() || html.trim_start().starts_with("<figure") => {
+                let typ = html_figure(env, chap_path, &html, &mut figure, &mut caption);
+                write!(writer, "{typ}")?;
+            }
+            Event::Html(html) | Event::InlineHtml(html)
+                if html.trim_start().starts_with("<img") =>
+            {
+                if let Some(typ) = html_image(env, chap_path, &html) {
+                    emit_inline(writer, &mut table, &mut caption, &typ)?;
+                }
+            }
+            Event::Html(html) => write!(writer, "{}", html_block(html, &mut html_state))?,
+            Event::InlineHtml(mark) if *mark == *HIGHLIGHT_MARK => {
+                // `highlight` is in typst 0.8 onwards
+                let typ = if in_highlight {
+                    "]"
+                } else if env.cfg.typst_at_least(0, 8) {
+                    "#highlight["
+                } else {
+                    "#box(fill: yellow)["
+                };
+                in_highlight = !in_highlight;
+                emit_inline(writer, &mut table, &mut caption, typ)?;
+            }
+            Event::InlineHtml(html) => {
+                let typ = inline_html(&html);
+                emit_inline(writer, &mut table, &mut caption, typ)?;
+            }
+            Event::SoftBreak | Event::HardBreak if caption.is_some() => {
+                emit_inline(writer, &mut table, &mut caption, " ")?
+            }
+            // the lines of a list item are indented to stay in the item
+            Event::SoftBreak if !task_items.is_empty() => {
+                write!(writer, "\n{}", "  ".repeat(lists.len()))?
+            }
+            Event::HardBreak if !task_items.is_empty() => {
+                write!(writer, "\n\n{}", "  ".repeat(lists.len()))?
+            }
+            Event::SoftBreak => writeln!(writer)?,
+            Event::HardBreak => write!(writer, "\n\n")?,
+            // the markdown of a `<figcaption>` is only its text
+            Event::Start(Tag::Paragraph) if figure.is_some() => {
+                if let Some(caption) = caption.as_mut().filter(|c| !c.is_empty()) {
+                    caption.push(' ');
+                }
+            }
+            Event::End(TagEnd::Paragraph) if figure.is_some() => (),
+            // only a caption paragraph is in a table
+            Event::Start(Tag::Paragraph) if table.is_some() => caption = Some(String::new()),
+            Event::End(TagEnd::Paragraph) if table.is_some() => {
+                if let Some(table) = &mut table {
+                    table.caption = caption.take();
+                }
+            }
+            Event::Start(Tag::Paragraph) if item_start => (),
+            Event::Start(Tag::Paragraph) if !task_items.is_empty() => {
+                // the later paragraphs of an item are indented to continue it
+                write!(writer, "\n{}", "  ".repeat(lists.len()))?
+            }
+            Event::Start(Tag::Paragraph) if !consec_par => {
+                write!(writer, "{}", env.emitter.paragraph_start())?
+            }
+            Event::End(TagEnd::Paragraph) => {
+                write!(writer, "{}", env.emitter.paragraph_end())?;
+                consec_par = true;
+                continue;
+            }
+            Event::Start(Tag::Strong) => {
+                if let Some(caption) = &mut caption {
+                    caption.push_str("#strong[");
+                } else if let Some(table) = &mut table {
+                    table.open_style("*", "#strong");
+                } else {
+                    write!(writer, "*")?
+                }
+            }
+            Event::End(TagEnd::Strong) => {
+                if let Some(caption) = &mut caption {
+                    caption.push(']');
+                } else if let Some(table) = &mut table {
+                    table.close_style();
+                } else {
+                    write!(writer, "*")?
+                }
+            }
+            Event::Start(Tag::Emphasis) => {
+                if let Some(caption) = &mut caption {
+                    caption.push_str("#emph[");
+                } else if let Some(table) = &mut table {
+                    table.open_style("_", "#emph");
+                } else {
+                    write!(writer, "_")?
+                }
+            }
+            Event::End(TagEnd::Emphasis) => {
+                if let Some(caption) = &mut caption {
+                    caption.push(']');
+                } else if let Some(table) = &mut table {
+                    table.close_style();
+                } else {
+                    write!(writer, "_")?
+                }
+            }
+            Event::Start(Tag::Strikethrough) => {
+                emit_inline(writer, &mut table, &mut caption, "#strike[")?
+            }
+            Event::End(TagEnd::Strikethrough) => {
+                emit_inline(writer, &mut table, &mut caption, "]")?
+            }
+            Event::Start(Tag::Subscript) => emit_inline(writer, &mut table, &mut caption, "#sub[")?,
+            Event::End(TagEnd::Subscript) => emit_inline(writer, &mut table, &mut caption, "]")?,
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }) => {
+                let dest_url = if link_type == LinkType::Email {
+                    format!("mailto:{dest_url}").into()
+                } else {
+                    dest_url
+                };
+                let dest_url = if env.cfg.bundle_assets {
+                    bundle_asset(env, chap_path, dest_url)
+                } else {
+                    dest_url
+                };
+                let slug_style = env.cfg.slug_style.unwrap_or_default();
+                // links without their own text refer to the heading, so
+                // typst writes its number or name
+                let label = internal_label(chap_path, &dest_url, slug_style)
+                    .filter(|_| env.cfg.auto_link_text);
+                let placeholder = label.as_ref().and_then(|_| {
+                    events
+                        .next_if(|e| matches!(e, Event::Text(t) if t.as_ref() == dest_url.as_ref()))
+                });
+                let typ = match label {
+                    Some(label) if events.next_if_eq(&Event::End(TagEnd::Link)).is_some() => {
+                        format!("#ref(<{label}>)")
+                    }
+                    _ => match placeholder {
+                        Some(Event::Text(t)) => {
+                            format!(
+                                "{}{}",
+                                format_internal_link(chap_path, dest_url, slug_style),
+                                escape_typst(t)
+                            )
+                        }
+                        // a link without text shows where it goes
+                        _ if internal_label(chap_path, &dest_url, slug_style).is_none()
+                            && events.next_if_eq(&Event::End(TagEnd::Link)).is_some() =>
+                        {
+                            let text = dest_url.trim_start_matches("mailto:").to_string();
+                            format!(
+                                "{}{}]",
+                                format_internal_link(chap_path, dest_url, slug_style),
+                                escape_typst(text.into())
+                            )
+                        }
+                        _ => format_internal_link(chap_path, dest_url, slug_style),
+                    },
+                };
+                emit_inline(writer, &mut table, &mut caption, &typ)?;
+            }
+            Event::Start(Tag::CodeBlock(ck)) => {
+                code_info = match &ck {
+                    CodeBlockKind::Fenced(info) => CodeInfo::parse(info),
+                    CodeBlockKind::Indented => CodeInfo::default(),
+                };
+                in_code = true;
+            }
+            Event::End(TagEnd::Link) => {
+                emit_inline(writer, &mut table, &mut caption, "]")?;
+            }
+            Event::End(TagEnd::CodeBlock) if code_info.lang == passthrough => {
+                // typst written by the author, used as it is
+                in_code = false;
+                write!(writer, "\n{code_text}\n")?;
+                code_text.clear();
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code = false;
+                if code_info.lang.is_empty() {
+                    let detected = detect_lang(&code_text).filter(|_| env.cfg.detect_code_lang);
+                    code_info.lang = detected
+                        .or(env.cfg.default_code_lang.as_deref())
+                        .unwrap_or_default()
+                        .to_string();
+                }
+                let mut typ = env.emitter.code_block(&code_info, &code_text);
+                if env.cfg.style_console && code_info.lang == "console" {
+                    typ = env.emitter.console_block(&typ);
+                }
+                if env.cfg.code_lang_caption && !code_info.lang.is_empty() {
+                    let name = escape_typst(lang_name(&code_info.lang).into());
+                    write!(writer, "{}", env.emitter.figure("raw", &typ, &name))?;
+                } else {
+                    write!(writer, "{typ}")?;
+                }
+                code_text.clear();
+            }
+            Event::Start(Tag::List(l)) => {
+                // a nested list is styled by its own items
+                if let Some(item @ true) = task_items.last_mut() {
+                    write!(writer, "]")?;
+                    *item = false;
+                }
+                writeln!(writer)?;
+                lists.push(l);
+            }
+            Event::Start(Tag::Item) => {
+                // typst nests the lists by their indentation
+                write!(writer, "{}", "  ".repeat(lists.len().saturating_sub(1)))?;
+                if let Some(Some(l)) = lists.last_mut() {
+                    write!(writer, "{l}. ")?;
+                    *l += 1;
+                } else {
+                    write!(writer, "- ")?;
+                }
+                task_items.push(false);
+                item_start = true;
+                continue;
+            }
+            Event::TaskListMarker(checked) => {
+                write!(writer, "{}", env.emitter.task_marker(checked))?;
+                if let (true, Some(style), Some(item)) =
+                    (checked, env.cfg.completed_task_style, task_items.last_mut())
+                {
+                    let func = match style {
+                        TaskStyle::Strike => "#strike",
+                        TaskStyle::Muted => "#text(fill: gray)",
+                    };
+                    write!(writer, "{func}[")?;
+                    *item = true;
+                }
+            }
+            Event::End(TagEnd::Item) => {
+                if task_items.pop().unwrap_or_default() {
+                    write!(writer, "]")?;
+                }
+                writeln!(writer)?;
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+            }
+            Event::Start(Tag::Heading { level, .. }) => {
+                let hl = match level {
+                    HeadingLevel::H1 => 1,
+                    HeadingLevel::H2 => 2,
+                    HeadingLevel::H3 => 3,
+                    HeadingLevel::H4 => 4,
+                    HeadingLevel::H5 => 5,
+                    HeadingLevel::H6 => 6,
+                } + chap_level;
+                write!(writer, "{}", env.emitter.heading_start(hl, number))?;
+                in_head = true;
+                head_text.clear();
+                head_label = false;
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                in_head = false;
+                let id = heading_slug(&head_text, env.cfg.slug_style.unwrap_or_default());
+                if !head_label && !id.is_empty() {
+                    // same as mdbook, repeated ids get a `-N` suffix
+                    let count = head_ids.entry(id.clone()).or_default();
+                    let id = if *count == 0 {
+                        id
+                    } else {
+                        format!("{id}-{count}")
+                    };
+                    *count += 1;
+                    let label = format!("{chap_id}:{id}");
+                    env.add_label(&label, chap_name);
+                    write!(writer, " <{label}>")?;
+                }
+                write!(writer, "{}", env.emitter.heading_end(number))?;
+            }
+            Event::Start(Tag::Image {
+                dest_url, title, ..
+            }) => {
+                let path = image_path(env, chap_path, &dest_url);
+                image = Some((path, title.to_string(), String::new()));
+                caption = Some(String::new());
+            }
+            Event::End(TagEnd::Image) => {
+                if let (Some((path, title, plain)), Some(alt)) = (image.take(), caption.take()) {
+                    // the title is meant as the caption, the alt text is
+                    // for when the image can't be seen
+                    let text = if title.is_empty() {
+                        alt
+                    } else {
+                        escape_typst(title.into())
+                    };
+                    let typ = env
+                        .emitter
+                        .image(&path, None, image_alt(env, &plain), &text);
+                    emit_inline(writer, &mut table, &mut caption, &typ)?;
+                }
+            }
+            Event::Start(Tag::Table(al)) => {
+                table = Some(MdTable {
+                    aligns: al
+                        .into_iter()
+                        .map(|a| match a {
+                            Alignment::None => "auto",
+                            Alignment::Left => "left",
+                            Alignment::Right => "right",
+                            Alignment::Center => "center",
+                        })
+                        .collect(),
+                    ..Default::default()
+                });
+            }
+            Event::Start(Tag::TableHead) => {
+                if let Some(table) = &mut table {
+                    table.on_cell = false;
+                }
+            }
+            Event::End(TagEnd::TableHead) => {
+                if let Some(table) = &mut table {
+                    table.on_cell = true;
+                }
+            }
+            Event::End(TagEnd::TableCell) => {
+                if let Some(table) = &mut table {
+                    let cell = table.thiscell.clone();
+                    table.thiscell.clear();
+                    if table.on_cell {
+                        table.cells.push(cell);
+                    } else {
+                        table.headers.push(cell);
+                    }
+                }
+            }
+            Event::End(TagEnd::Table) => {
+                if let Some(mut table) = table.take() {
+                    // `table.header` is in typst 0.11 onwards, before that
+                    // the header is the first row in bold
+                    if !env.cfg.typst_at_least(0, 11) {
+                        let mut cells: Vec<String> =
+                            table.headers.drain(..).map(|h| format!("*{h}*")).collect();
+                        cells.append(&mut table.cells);
+                        table.cells = cells;
+                    }
+                    let mut typ = env
+                        .emitter
+                        .table(&table.aligns, &table.headers, &table.cells);
+                    if let Some(caption) = &table.caption {
+                        typ = env.emitter.figure("table", &typ, caption);
+                    }
+                    // a table longer than a page can't help breaking
+                    let rows = table.cells.len() / table.aligns.len().max(1);
+                    if env.cfg.table_avoid_break && rows <= MAX_UNBROKEN_ROWS {
+                        write!(writer, "\n#block(breakable: false)[{typ}]\n")?
+                    } else {
+                        write!(writer, "{typ}")?
+                    }
+                }
+            }
+
+            Event::Start(Tag::BlockQuote(None)) => {
+                // the quote is rendered on its own to pass it to `#quote`
+                let mut inner = Vec::new();
+                let mut depth = 1;
+                for e in events.by_ref() {
+                    match e {
+                        Event::Start(Tag::BlockQuote(_)) => depth += 1,
+                        Event::End(TagEnd::BlockQuote(_)) if depth == 1 => break,
+                        Event::End(TagEnd::BlockQuote(_)) => depth -= 1,
+                        _ => (),
+                    }
+                    inner.push(e);
+                }
+                let attribution = if env.cfg.quote_attribution {
+                    take_attribution(&mut inner)
+                } else {
+                    None
+                };
+                let mut body = Vec::new();
+                write_events(&mut body, env, inner, chap, footnotes)?;
+                let attribution = match attribution {
+                    Some(events) => {
+                        let mut buf = Vec::new();
+                        write_events(&mut buf, env, events, chap, footnotes)?;
+                        Some(String::from_utf8_lossy(&buf).trim().to_string())
+                    }
+                    None => None,
+                };
+                let typ = env
+                    .emitter
+                    .quote(&String::from_utf8_lossy(&body), attribution.as_deref());
+                write!(writer, "{typ}")?;
+            }
+            Event::Start(Tag::BlockQuote(Some(kind))) => {
+                let kind = match kind {
+                    BlockQuoteKind::Note => "note",
+                    BlockQuoteKind::Tip => "tip",
+                    BlockQuoteKind::Important => "important",
+                    BlockQuoteKind::Warning => "warning",
+                    BlockQuoteKind::Caution => "caution",
+                };
+                write!(writer, "{}", env.emitter.alert_start(kind))?;
+            }
+            Event::End(TagEnd::BlockQuote(Some(_))) => {
+                write!(writer, "{}", env.emitter.alert_end())?;
+            }
+            // typst has its own math syntax, the formulas are used as is
+            Event::InlineMath(math) => {
+                emit_inline(writer, &mut table, &mut caption, &format!("${math}$"))?
+            }
+            Event::DisplayMath(math) => {
+                let typ = format!("$ {} $", math.trim());
+                emit_inline(writer, &mut table, &mut caption, &typ)?
+            }
+            Event::Rule => {
+                let typ = env.emitter.rule();
+                emit_inline(writer, &mut table, &mut caption, &typ)?;
+            }
+            Event::FootnoteReference(label) => {
+                let id = format!("fn:{chap_id}:{}", footnote_id(&label));
+                let note = match footnotes.get(label.as_ref()) {
+                    // the same note is shown once, and referred to after that
+                    Some(_) if !env.shown_notes.borrow_mut().insert(id.clone()) => {
+                        env.emitter.footnote_ref(&id)
+                    }
+                    Some(evts) => {
+                        // the note can refer to other notes in turn
+                        let mut buf = Vec::new();
+                        write_events(&mut buf, env, evts.clone(), chap, footnotes)?;
+                        let note = String::from_utf8_lossy(&buf).trim().to_string();
+                        env.emitter.footnote(&note, &id)
+                    }
+                    None => format!("[^{label}]"),
+                };
+                emit_inline(writer, &mut table, &mut caption, &note)?;
+            }
+            _ => (),
+        }
+        consec_par = false;
+        item_start = false;
+    }
+
+    Ok(())
+}
+
+/// Guess the language of a code block without one from the words
+/// typical for it, `None` when nothing stands out
+fn detect_lang(code: &str) -> Option<&'static str> {
+    let trimmed = code.trim_start();
+    let has_line = |prefix: &str| code.lines().any(|l| l.trim_start().starts_with(prefix));
+    let lang = if trimmed.starts_with("#!") {
+        if trimmed.starts_with("#!/usr/bin/env python") {
+            "python"
+        } else {
+            "sh"
+        }
+    } else if (has_line("fn ") || has_line("pub fn ") || has_line("use "))
+        && (code.contains("let ") || code.contains("->") || code.contains("::"))
+    {
+        "rust"
+    } else if (has_line("def ") || has_line("class ")) && code.contains("):") {
+        "python"
+    } else if has_line("#include") {
+        "c"
+    } else if has_line("function ") || code.contains("=> {") || has_line("const ") {
+        "javascript"
+    } else if trimmed.starts_with('<') && code.contains("</") {
+        "html"
+    } else if (trimmed.starts_with('{') || trimmed.starts_with('[')) && code.contains("\":") {
+        "json"
+    } else if has_line("$ ") {
+        "console"
+    } else {
+        return None;
+    };
+    Some(lang)
+}
+
+/// Typst markup of the inline elements of a line of markdown, for the
+/// titles that aren't in a chapter
+///
+/// The function forms like `#strong[..]` are used, so the markup is safe
+/// in any content block; a line that looks like a list item keeps its marker.
+fn inline_markdown(env: &Env, md: &str) -> String {
+    let mut out = String::new();
+    for event in Parser::new_ext(md, Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Text(t) => out.push_str(&escape_typst(t)),
+            Event::Code(c) => out.push_str(&env.emitter.inline_code(&c)),
+            Event::Start(Tag::List(Some(n))) => out.push_str(&format!("{n}\\. ")),
+            Event::Start(Tag::List(None)) => out.push_str("\\- "),
+            Event::Start(Tag::Strong) => out.push_str("#strong["),
+            Event::Start(Tag::Emphasis) => out.push_str("#emph["),
+            Event::Start(Tag::Strikethrough) => out.push_str("#strike["),
+            Event::End(TagEnd::Strong | TagEnd::Emphasis | TagEnd::Strikethrough) => out.push(']'),
+            Event::SoftBreak | Event::HardBreak => out.push(' '),
+            _ => (),
+        }
+    }
+    out
+}
+
+/// Text of a line of markdown without its inline markup
+fn plain_text(md: &str) -> String {
+    let mut out = String::new();
+    for event in Parser::new_ext(md, Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Text(t) | Event::Code(t) => out.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => out.push(' '),
+            _ => (),
+        }
+    }
+    out
+}
+
+/// Take the attribution line at the end of a block quote, like
+/// `— Author`, out of the events of the quote
+///
+/// The attribution is either the last paragraph of the quote, or the
+/// last line of it, and starts with an em dash or two or three hyphens.
+fn take_attribution<'a>(events: &mut Vec<Event<'a>>) -> Option<Vec<Event<'a>>> {
+    let is_dash = |e: &Event| matches!(e, Event::Text(t) if ["—", "--", "―"].iter().any(|d| t.starts_with(d)));
+    // the paragraph has to be the last thing in the quote
+    if events.last() != Some(&Event::End(TagEnd::Paragraph)) {
+        return None;
+    }
+    let para = events
+        .iter()
+        .rposition(|e| *e == Event::Start(Tag::Paragraph))?;
+    let start = if is_dash(events.get(para + 1)?) {
+        para
+    } else {
+        let line = events[para..]
+            .iter()
+            .rposition(|e| matches!(e, Event::SoftBreak | Event::HardBreak))?
+            + para;
+        if !is_dash(events.get(line + 1)?) {
+            return None;
+        }
+        line
+    };
+    let mut attribution: Vec<Event> = events.drain(start..).collect();
+    if start != para {
+        // the line break before the attribution ends the quote paragraph
+        events.push(Event::End(TagEnd::Paragraph));
+    }
+    // only the inline events of the line, without its dash
+    attribution.retain(|e| {
+        !matches!(
+            e,
+            Event::Start(Tag::Paragraph)
+                | Event::End(TagEnd::Paragraph)
+                | Event::SoftBreak
+                | Event::HardBreak
+        )
+    });
+    if let Some(Event::Text(t)) = attribution.first_mut() {
+        *t = t
+            .trim_start_matches(['—', '-', '―'])
+            .trim_start()
+            .to_string()
+            .into();
+    }
+    Some(attribution)
+}
+
+/// Name of a code block language for its caption
+fn lang_name(lang: &str) -> String {
+    let name = match lang.to_lowercase().as_str() {
+        "rs" | "rust" => "Rust",
+        "py" | "python" => "Python",
+        "js" | "javascript" => "JavaScript",
+        "ts" | "typescript" => "TypeScript",
+        "sh" | "bash" | "shell" => "Shell",
+        "console" => "Console",
+        "c" => "C",
+        "cpp" | "c++" => "C++",
+        "toml" => "TOML",
+        "yaml" | "yml" => "YAML",
+        "json" => "JSON",
+        "html" => "HTML",
+        "css" => "CSS",
+        "md" | "markdown" => "Markdown",
+        "typ" | "typst" => "Typst",
+        _ => return lang.to_string(),
+    };
+    name.to_string()
+}
+
+/// Write inline typst to the innermost open container: the image
+/// caption, the table cell, or else the output
+fn emit_inline(
+    writer: &mut impl Write,
+    table: &mut Option<MdTable>,
+    caption: &mut Option<String>,
+    typ: &str,
+) -> std::io::Result<()> {
+    if let Some(caption) = caption {
+        caption.push_str(typ);
+    } else if let Some(table) = table {
+        table.thiscell.push_str(typ);
+    } else {
+        write!(writer, "{typ}")?
+    }
+    Ok(())
+}
+
+/// The heading text with its explicit `{ #id }` as a label, and the label
+fn maybe_label(chap_name: &str, text: pulldown_cmark::CowStr) -> (String, Option<String>) {
+    if let Some((pre, post)) = text.split_once(" { #") {
+        // the label can't have spaces, the links to it are made the same way
+        let label = slug(post.trim().trim_end_matches('}').trim());
+        let label = format!("{}:{label}", slug(chap_name));
+        let text = escape_typst(pre.into());
+        (format!("{text} <{label}>"), Some(label))
+    } else {
+        (escape_typst(text), None)
+    }
+}
+
+/// Replace the tabs in body text with spaces, or a typst spacing
+///
+/// A number for `width` is the number of spaces, anything else is used
+/// as a typst length in `#h()`. Without it a tab is a single space.
+fn expand_tabs(text: String, width: Option<&str>) -> String {
+    if !text.contains('\t') {
+        return text;
+    }
+    let tab = match width {
+        None => " ".to_string(),
+        Some(w) => match w.trim().parse::<usize>() {
+            Ok(n) => " ".repeat(n),
+            Err(_) => format!("#h({})", w.trim()),
+        },
+    };
+    text.replace('\t', &tab)
+}
+
+/// Replace the pandoc style `^text^` (or `~text~`) spans of `mark` with
+/// `func`, the text in between can't have spaces; `mark` is as it is in
+/// the escaped text, like `\~`
+fn script_marks(text: &str, mark: &str, func: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find(mark) {
+        out.push_str(&rest[..pos]);
+        let post = &rest[pos + mark.len()..];
+        match post.find(mark) {
+            Some(end) if end > 0 && !post[..end].contains(char::is_whitespace) => {
+                out.push_str(&format!("{func}[{}]", &post[..end]));
+                rest = &post[end + mark.len()..];
+            }
+            _ => {
+                out.push_str(mark);
+                rest = post;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Id of a heading from its text, as the links to it are written
+fn heading_slug(text: &str, style: SlugStyle) -> String {
+    match style {
+        // https://github.com/rust-lang/mdBook `normalize_id`
+        SlugStyle::Mdbook => text
+            .chars()
+            .filter_map(|c| {
+                if c.is_alphanumeric() || c == '_' || c == '-' {
+                    Some(c.to_ascii_lowercase())
+                } else if c.is_whitespace() {
+                    Some('-')
+                } else {
+                    None
+                }
+            })
+            .collect(),
+        // github drops the punctuation but keeps the unicode lowercase
+        SlugStyle::Github => text
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter_map(|c| match c {
+                ' ' => Some('-'),
+                c if c.is_alphanumeric() || c == '_' || c == '-' => Some(c),
+                _ => None,
+            })
+            .collect(),
+        // pandoc keeps `.` and drops everything before the first letter
+        SlugStyle::Pandoc => {
+            let id: String = text
+                .trim()
+                .to_lowercase()
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join("-")
+                .chars()
+                .filter(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                .skip_while(|c| !c.is_alphabetic())
+                .collect();
+            if id.is_empty() {
+                "section".to_string()
+            } else {
+                id
+            }
+        }
+    }
+}
+
+/// Part of a label made from a name; spaces, and the characters typst
+/// doesn't allow in labels, are replaced with `_`
+fn slug(name: &str) -> String {
+    label_chars(&name.to_lowercase())
+}
+
+/// Replace the characters typst doesn't allow in labels with `_`
+fn label_chars(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '.' | ':') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Footnote labels can have characters that typst labels can't
+fn footnote_id(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Copy a link to a local file (other than chapters) into the `assets`
+/// directory of the build, and return the link to the copy
+///
+/// Links that are not local files are returned as they are.
+fn bundle_asset<'a>(
+    env: &Env,
+    chap_path: Option<&Path>,
+    link: pulldown_cmark::CowStr<'a>,
+) -> pulldown_cmark::CowStr<'a> {
+    let file = link.split(['#', '?']).next().unwrap_or_default();
+    if file.is_empty() || file.contains(':') || file.starts_with('/') || file.ends_with(".md") {
+        return link;
+    }
+    let rel = source_path(chap_path, file);
+    let src = env.src_dir.join(&rel);
+    if !src.is_file() {
+        return link;
+    }
+    let dest = env.dest_dir.join("assets").join(&rel);
+    if let Some(dir) = dest.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(e) = std::fs::copy(&src, &dest) {
+        eprintln!("Warning: could not copy {src:?} to {dest:?}: {e}");
+        return link;
+    }
+    let rel = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("assets/{rel}").into()
+}
+
+/// Path in the book source of a file linked from the chapter at
+/// `chap_path`, links starting with `/` are from the source directory
+fn source_path(chap_path: Option<&Path>, link: &str) -> PathBuf {
+    let mut rel = PathBuf::new();
+    let base = chap_path.and_then(|p| p.parent()).unwrap_or(Path::new(""));
+    for comp in base.join(link).components() {
+        match comp {
+            std::path::Component::Normal(c) => rel.push(c),
+            std::path::Component::ParentDir => {
+                rel.pop();
+            }
+            _ => (),
+        }
+    }
+    rel
+}
+
+/// Label of the heading a link to another chapter points to, or of the
+/// chapter title when the link has no heading id; the link is relative
+/// to the chapter at `chap_path`
+///
+/// The heading id is normalized with the `style` of the heading labels,
+/// so `#Über` points to the heading `Über` in every style.
+fn internal_label(chap_path: Option<&Path>, link: &str, style: SlugStyle) -> Option<String> {
+    if link.contains("://") {
+        return None;
+    }
+    let (file, id) = match link.split_once('#') {
+        Some((file, id)) => (file, Some(id)),
+        None => (link.split('?').next().unwrap_or_default(), None),
+    };
+    if !file.ends_with(".md") {
+        return None;
+    }
+    let path = source_path(chap_path, file);
+    Some(match id {
+        Some(id) => {
+            let id = heading_slug(&id.replace("%20", " "), style);
+            format!("{}:{}", chapter_id(&path), label_chars(&id))
+        }
+        None => chapter_label(&path),
+    })
+}
+
+/// Label of a chapter title, made from its file path
+fn chapter_label(path: &Path) -> String {
+    format!("chapter:{}", chapter_id(path))
+}
+
+/// Id of a chapter in the labels, its whole path in the book source
+/// without the extension, so `a/README.md` and `b/README.md` differ
+fn chapter_id(path: &Path) -> String {
+    let parts: Vec<String> = path
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    slug(&parts.join("/"))
+}
+
+fn format_internal_link(
+    chap_path: Option<&Path>,
+    link: pulldown_cmark::CowStr,
+    style: SlugStyle,
+) -> String {
+    if let Some(label) = internal_label(chap_path, &link, style) {
+        return format!("#link(<{label}>)[");
+    }
+    format!("#link({})[", typst_string(&link))
+}
+
+/// Escape the characters that typst markup would treat as syntax,
+/// like `@` for references, `$` for math and `//` for comments
+///
+/// The heading and list markers (`=`, `-`, `+`, `/` and `1.`) are only
+/// syntax at the start of a line, so they are only escaped there.
+fn escape_typst(text: pulldown_cmark::CowStr) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let marker = line_marker(line);
+        for (i, c) in line.char_indices() {
+            let escape = match c {
+                '\\' | '*' | '#' | '_' | '`' | '@' | '$' | '<' | '>' | '[' | ']' | '~' => true,
+                // typst makes links of the urls, their `//` is no comment
+                '/' if line[i + 1..].starts_with(['/', '*']) => {
+                    !out.ends_with("http:") && !out.ends_with("https:")
+                }
+                _ => marker == Some(i),
+            };
+            if escape {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Position of the character that makes a line a typst heading or list
+/// item, like the `-` of `- item` or the `.` of `2. item`
+fn line_marker(line: &str) -> Option<usize> {
+    let start = line.len() - line.trim_start().len();
+    let rest = &line[start..];
+    let token = &rest[..rest.find(char::is_whitespace).unwrap_or(rest.len())];
+    if matches!(token, "-" | "+" | "/") || (!token.is_empty() && token.chars().all(|c| c == '=')) {
+        return Some(start);
+    }
+    let digits = token.strip_suffix('.')?;
+    (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+        .then_some(start + digits.len())
+}
+
+/// Copy a local image into the build directory, at its path in the book
+/// source, and return the path of the copy
+///
+/// The image is converted instead if its extension is in `image-convert`,
+/// and the copy gets the new extension. On failure a warning is shown and
+/// the image is copied as it is. Without a build directory nothing is
+/// copied.
+fn copy_image(env: &Env, path: PathBuf) -> PathBuf {
+    if let Some(target) = env.cfg.image_convert.as_ref().and_then(|conv| {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        conv.get(&ext)
+    }) {
+        let out = path.with_extension(target);
+        let dest = env.dest_dir.join(&out);
+        if let Some(dir) = dest.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let converter = env
+            .cfg
+            .image_converter
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("magick"));
+        match std::process::Command::new(&converter)
+            .arg(env.src_dir.join(&path))
+            .arg(&dest)
+            .status()
+        {
+            Ok(status) if status.success() => return out,
+            Ok(status) => eprintln!(
+                "Warning: {converter:?} failed to convert {path:?} ({status}), using it as is"
+            ),
+            Err(e) => eprintln!("Warning: could not run {converter:?} to convert {path:?}: {e}"),
+        }
+    }
+    let src = env.src_dir.join(&path);
+    let dest = env.dest_dir.join(&path);
+    // a missing image is left for typst to report
+    if env.dest_dir.as_os_str().is_empty() || src == dest || !src.is_file() {
+        return path;
+    }
+    if let Some(dir) = dest.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(e) = std::fs::copy(&src, &dest) {
+        eprintln!("Warning: could not copy {src:?} to {dest:?}: {e}");
+    }
+    path
+}
+
+fn typst_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// typst always expects `/` as the separator, even on windows
+fn typst_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if std::path::MAIN_SEPARATOR == '/' {
+        typst_string(&path)
+    } else {
+        typst_string(&path.replace(std::path::MAIN_SEPARATOR, "/"))
+    }
+}
+
+/// Path of an image for typst, from the root of the build directory the
+/// image is copied to
+///
+/// The path starts with `/`, so it is the same in all the files of the
+/// book, wherever they are; the remote images are left as they are.
+fn image_path(env: &Env, chap_path: Option<&Path>, url: &str) -> String {
+    if url.contains("://") {
+        return typst_string(url);
+    }
+    let path = copy_image(env, source_path(chap_path, url));
+    typst_path(&Path::new("/").join(path))
+}
+
+/// The image of a html `<figure>`, and its caption once it is closed
+#[derive(Default)]
+struct HtmlFigure {
+    /// path and width of the `<img>`
+    image: Option<(String, Option<String>)>,
+    /// the plain alt text of the `<img>`
+    alt: String,
+    caption: Option<String>,
+}
+
+/// Follow a html `<figure>` over the html events of its tags
+///
+/// The markdown between `<figcaption>` and `</figcaption>` is collected
+/// in `caption` like the alt text of the markdown images, and the image
+/// is written as a figure at `</figure>` with it as the caption.
+fn html_figure(
+    env: &Env,
+    chap_path: Option<&Path>,
+    html: &str,
+    figure: &mut Option<HtmlFigure>,
+    caption: &mut Option<String>,
+) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+    loop {
+        // text in the same html events as the caption tags, with its
+        // inline markdown
+        let start = rest.find('<').unwrap_or(rest.len());
+        if let Some(caption) = caption {
+            caption.push_str(&inline_markdown(env, &rest[..start].replace('\n', " ")));
+        }
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start..start + end + 1];
+        rest = &rest[start + end + 1..];
+        let name: String = tag[1..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '/')
+            .collect();
+        match name.to_lowercase().as_str() {
+            "figure" => *figure = Some(HtmlFigure::default()),
+            "img" => {
+                if let (Some(fig), Some(src)) = (figure.as_mut(), html_attr(tag, "src")) {
+                    let width = html_attr(tag, "width").and_then(html_length);
+                    fig.image = Some((image_path(env, chap_path, src), width));
+                    fig.alt = html_attr(tag, "alt").unwrap_or_default().to_string();
+                }
+            }
+            "figcaption" => *caption = Some(String::new()),
+            "/figcaption" => {
+                if let Some(fig) = figure.as_mut() {
+                    fig.caption = caption.take();
+                }
+            }
+            "/figure" => {
+                let Some(fig) = figure.take() else {
+                    continue;
+                };
+                let text = fig.caption.or(caption.take());
+                let text = text.unwrap_or_else(|| escape_typst(fig.alt.as_str().into()));
+                let text = text.trim();
+                match fig.image {
+                    Some((path, width)) => out.push_str(&env.emitter.image(
+                        &path,
+                        width.as_deref(),
+                        image_alt(env, &fig.alt),
+                        text,
+                    )),
+                    // a figure of something else keeps its caption as text
+                    None => out.push_str(&format!("\n{text}\n")),
+                }
+            }
+            // the inline tags of the caption are dropped
+            _ => (),
+        }
+    }
+    out
+}
+
+/// A html `<img>` tag as a figure like the markdown images; the web
+/// only attributes like `loading` are ignored
+fn html_image(env: &Env, chap_path: Option<&Path>, tag: &str) -> Option<String> {
+    let src = html_attr(tag, "src")?;
+    let alt = html_attr(tag, "alt").unwrap_or_default();
+    let caption = html_attr(tag, "title")
+        .filter(|t| !t.is_empty())
+        .unwrap_or(alt);
+    let width = html_attr(tag, "width").and_then(html_length);
+    let path = image_path(env, chap_path, src);
+    Some(env.emitter.image(
+        &path,
+        width.as_deref(),
+        image_alt(env, alt),
+        &escape_typst(caption.into()),
+    ))
+}
+
+/// The alt text of an image for `image(alt:)`, `None` when it is empty
+/// or the typst version is older than the `alt` of the images
+fn image_alt<'a>(env: &Env, alt: &'a str) -> Option<&'a str> {
+    (!alt.trim().is_empty() && env.cfg.typst_at_least(0, 11)).then_some(alt.trim())
+}
+
+/// Typst length of a html size, plain numbers are pixels
+fn html_length(size: &str) -> Option<String> {
+    let size = size.trim();
+    if let Some(percent) = size.strip_suffix('%') {
+        let percent: f64 = percent.parse().ok()?;
+        return Some(format!("{percent}%"));
+    }
+    let px: f64 = size.strip_suffix("px").unwrap_or(size).parse().ok()?;
+    Some(format!("{}pt", px * 0.75))
+}
+
+/// Typst markup for the inline html tags, others are dropped
+fn inline_html(html: &str) -> &'static str {
+    match html.trim().to_lowercase().as_str() {
+        "<u>" => "#underline[",
+        "</u>" => "]",
+        _ => "",
+    }
+}
+
+/// State of the html tags that span multiple html events
+#[derive(Default)]
+struct HtmlState {
+    /// open `<details>` tags, `true` if it has the `open` attribute
+    details: Vec<bool>,
+    /// open `<div>` tags, `true` if it was made into a `#htmlblock`
+    divs: Vec<bool>,
+    /// render the tab groups of mdbook-tabs as titled sections
+    tabs: bool,
+    /// inside the `<nav>` with the buttons of a tab group
+    tab_nav: bool,
+}
+
+/// Turn the `<div class="...">` tags into `#htmlblock`s
+///
+/// The divs can be nested and span over multiple html events, so the
+/// closing tags are matched with the open ones in `state`. Divs without
+/// a class are dropped along with their closing tags.
+fn html_divs(html: &str, state: &mut HtmlState) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+    loop {
+        let open = rest
+            .match_indices("<div")
+            .find(|(i, _)| rest[i + 4..].starts_with([' ', '>', '\n']))
+            .map(|(i, _)| i);
+        let close = rest.find("</div>");
+        match (open, close) {
+            (Some(o), c) if c.is_none_or(|c| o < c) => {
+                let Some(end) = rest[o..].find('>') else {
+                    break;
+                };
+                out.push_str(&rest[..o]);
+                let tag = &rest[o..o + end];
+                let class = html_attr(tag, "class");
+                let tab_class = |name: &str| {
+                    state.tabs && class.is_some_and(|c| c.split_whitespace().any(|c| c == name))
+                };
+                if tab_class("mdbook-tabs-container") {
+                    state.divs.push(false);
+                } else if tab_class("mdbook-tab-content") {
+                    // each tab follows the previous one with its name as the title
+                    let name = html_attr(tag, "data-tabname").unwrap_or_default();
+                    out.push_str(&format!("*{}*\n\n", escape_typst(name.into())));
+                    state.divs.push(false);
+                } else if let Some(class) = class {
+                    out.push_str(&format!("#htmlblock({})[", typst_string(class)));
+                    state.divs.push(true);
+                } else {
+                    state.divs.push(false);
+                }
+                rest = &rest[o + end + 1..];
+            }
+            (_, Some(c)) => {
+                out.push_str(&rest[..c]);
+                if state.divs.pop().unwrap_or(true) {
+                    out.push(']');
+                }
+                rest = &rest[c + 6..];
+            }
+            (_, None) => break,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Value of the attribute `name` in a html tag, like `class="x"`
+fn html_attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    tag.split_once(&format!(" {name}=\""))
+        .and_then(|(_, v)| v.split_once('"'))
+        .map(|(v, _)| v)
+}
+
+/// Remove the buttons of the mdbook-tabs tab groups, they don't do
+/// anything on paper
+///
+/// The markdown parser gives each line of the html as its own event, so
+/// a `<nav>` still open at the end is dropped up to its end in `state`.
+fn drop_tab_nav(html: &str, state: &mut HtmlState) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+    loop {
+        if state.tab_nav {
+            let Some(end) = rest.find("</nav>") else {
+                return out;
+            };
+            rest = &rest[end + 6..];
+            state.tab_nav = false;
+        }
+        let Some(start) = rest.find("<nav class=\"mdbook-tabs\"") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        state.tab_nav = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+fn html_block(html: pulldown_cmark::CowStr, state: &mut HtmlState) -> String {
+    let html = if state.tabs {
+        drop_tab_nav(&html, state).into()
+    } else {
+        html
+    };
+    // <details> can't be collapsed on paper, so it is a block with the
+    // summary as its title, unless it is already expanded with `open`
+    if let Some(attrs) = html.trim().strip_prefix("<details") {
+        let open = attrs
+            .trim_end_matches('>')
+            .split_whitespace()
+            .any(|a| a == "open" || a.starts_with("open="));
+        state.details.push(open);
+        return if open {
+            String::new()
+        } else {
+            "#htmlblock(\"details\")[".to_string()
+        };
+    }
+    if html.trim() == "</details>" {
+        return match state.details.pop() {
+            Some(true) => String::new(),
+            _ => "]".to_string(),
+        };
+    }
+    if let Some(summary) = html
+        .trim()
+        .strip_prefix("<summary>")
+        .and_then(|s| s.strip_suffix("</summary>"))
+    {
+        let summary = escape_typst(summary.into());
+        return match state.details.last() {
+            Some(true) => format!("*{summary}*\n\n"),
+            _ => format!("*▸ {summary}*\\\n"),
+        };
+    }
+
+    // <div class="right">
+    if html.contains("<div") || html.contains("</div>") {
+        return html_divs(html.trim(), state);
+    }
+
+    if html.trim().starts_with("<!--") {
+        return String::new();
+    }
+
+    match html.trim() {
+        "<center>" => "".to_string(),
+        "</center>" => "".to_string(),
+        _ => html.to_string(),
+    }
+}
@@ -0,0 +1,390 @@
+//! Checks on the typst of whole books, for the options that work on the
+//! chapters, parts and files rather than the markdown
+
+use mdbook_renderer::RenderContext;
+use mdbook_renderer::book::{Book, BookItem, Chapter, SectionNumber};
+use mdbook_typst::TypstBook;
+use mdbook_typst::config::Config;
+use std::path::Path;
+
+/// A numbered chapter with the file `path` in the book source
+fn chapter(name: &str, number: &[u32], content: &str, path: &str) -> BookItem {
+    let mut chap = Chapter::new(name, content.to_string(), path, Vec::new());
+    chap.number = Some(SectionNumber::new(number));
+    BookItem::Chapter(chap)
+}
+
+/// Render the book of `items` in `root` with the `[output.typst]`
+/// options in `toml`
+fn render(root: &Path, toml: &str, items: Vec<BookItem>) -> anyhow::Result<TypstBook> {
+    let config = format!("[output.typst]\n{toml}").parse().unwrap();
+    let book = Book::new_with_items(items);
+    let ctx = RenderContext::new(root, book, config, root.join("book"));
+    let cfg: Config = ctx.config.get("output.typst")?.unwrap_or_default();
+    mdbook_typst::render_book(&ctx, &cfg)
+}
+
+#[test]
+fn demote_extra_h1() {
+    let md = "# Title\n\n## Sub\n\n# Second\n\nText\n";
+    let items = || vec![chapter("Title", &[1], md, "title.md")];
+    let typ = render(Path::new("."), "demote-extra-h1 = true", items())
+        .unwrap()
+        .main;
+    // the first title is the chapter title, the others are under it
+    assert!(!typ.contains("== Title"), "{typ}");
+    assert!(typ.contains("\n=== Sub <title:sub>"), "{typ}");
+    assert!(typ.contains("\n== Second <title:second>"), "{typ}");
+    // without it all the titles are kept at the chapter level
+    let typ = render(Path::new("."), "", items()).unwrap().main;
+    assert!(typ.contains("\n== Title <title:title>"), "{typ}");
+    assert!(typ.contains("\n== Second <title:second>"), "{typ}");
+}
+
+#[test]
+fn part_label() {
+    let items = vec![
+        BookItem::PartTitle("Part *One* & $2".to_string()),
+        chapter(
+            "Intro",
+            &[1],
+            "```typst-raw\nSee @part:part-one--2.\n```\n",
+            "intro.md",
+        ),
+    ];
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    // the label is made from the text, like the heading ids
+    assert!(
+        typ.contains(
+            "#figure(kind: \"part\", supplement: [Part], outlined: false, bookpart()[Part #emph[One] & \\$2]) <part:part-one--2>"
+        ),
+        "{typ}"
+    );
+    assert!(typ.contains("See @part:part-one--2."), "{typ}");
+}
+
+#[test]
+fn front_and_back_matter() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("front.typ"), "// dedication").unwrap();
+    std::fs::write(root.path().join("back.typ"), "// colophon").unwrap();
+    let toml = "front-matter = \"front.typ\"\nback-matter = \"back.typ\"";
+    let items = vec![chapter("Intro", &[1], "Text", "intro.md")];
+    let typ = render(root.path(), toml, items).unwrap().main;
+    let pos = |s: &str| typ.find(s).unwrap_or_else(|| panic!("no {s:?} in {typ}"));
+    // the front matter comes after the prelude, the back matter at the end
+    assert!(pos("#outline") < pos("// dedication"));
+    assert!(pos("// dedication") < pos("[Intro]"));
+    assert!(pos("[Intro]") < pos("// colophon"));
+    assert!(typ.trim_end().ends_with("// colophon"));
+}
+
+#[test]
+fn weak_part_breaks() {
+    let items = vec![
+        chapter("One", &[1], "Text", "one.md"),
+        BookItem::Separator,
+        BookItem::PartTitle("Second".to_string()),
+        chapter("Two", &[2], "Text", "two.md"),
+    ];
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    assert!(
+        typ.contains("\n#pagebreak(weak: true)\n#set page(header:[#h(1fr) Second])"),
+        "{typ}"
+    );
+    // only the outline ends with a hard break
+    assert_eq!(typ.matches("#pagebreak()").count(), 1, "{typ}");
+}
+
+#[test]
+fn restart_numbering_per_part() {
+    let items = || {
+        vec![
+            BookItem::PartTitle("First".to_string()),
+            chapter("One", &[1], "Text", "one.md"),
+            BookItem::PartTitle("Second".to_string()),
+            chapter("Two", &[2], "Text", "two.md"),
+        ]
+    };
+    let reset = "]) <part:second>\n#counter(heading).update(0)\n";
+    let typ = render(Path::new("."), "restart-numbering-per-part = true", items())
+        .unwrap()
+        .main;
+    assert_eq!(
+        typ.matches("#counter(heading).update(0)").count(),
+        2,
+        "{typ}"
+    );
+    assert!(typ.contains(reset), "{typ}");
+    let typ = render(Path::new("."), "", items()).unwrap().main;
+    assert!(!typ.contains("#counter(heading)"), "{typ}");
+}
+
+#[test]
+fn compact_output() {
+    let md = std::fs::read_to_string("tests/fixtures/nested_list.md").unwrap();
+    let items = || vec![chapter("Lists", &[1], &md, "lists.md")];
+    let full = render(Path::new("."), "", items()).unwrap().main;
+    let compact = render(Path::new("."), "compact-output = true", items())
+        .unwrap()
+        .main;
+    assert!(compact.lines().count() < full.lines().count());
+    assert!(!compact.contains("\n\n\n"), "{compact}");
+    // only the blank lines are different
+    let text = |typ: &str| {
+        typ.lines()
+            .filter(|l| !l.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(text(&compact), text(&full));
+}
+
+#[test]
+fn enum_delimiter() {
+    let items = || vec![chapter("Steps", &[1], "1) one\n2) two\n", "steps.md")];
+    let typ = render(Path::new("."), "enum-delimiter = \")\"", items())
+        .unwrap()
+        .main;
+    assert!(typ.contains("#set enum(numbering: \"1)\")\n"), "{typ}");
+    assert!(typ.contains("1. one\n2. two\n"), "{typ}");
+    let typ = render(Path::new("."), "", items()).unwrap().main;
+    assert!(!typ.contains("#set enum"), "{typ}");
+}
+
+#[test]
+fn chapter_front_matter() {
+    let md = "---\ntypst_paper: \"a3\"\n---\n\n# Big\n\nText\n";
+    let items = vec![chapter("Big", &[1], md, "big.md")];
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    assert!(typ.contains("\n#page(paper: \"a3\")[\n"), "{typ}");
+    assert!(!typ.contains("typst_paper"), "{typ}");
+    // a chapter starting with a rule keeps all its text
+    let md = "---\n\nA rule first\n\nkey: value\n\n---\n\nAfter\n";
+    let items = vec![chapter("Rule", &[1], md, "rule.md")];
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    assert!(!typ.contains("#page(paper"), "{typ}");
+    for text in ["#line(length: 100%)", "A rule first", "key: value", "After"] {
+        assert!(typ.contains(text), "no {text:?} in {typ}");
+    }
+}
+
+#[test]
+fn split_numbering() {
+    let items = vec![
+        chapter("One", &[1], "![first](a.png)", "one.md"),
+        chapter("Two", &[2], "![second](b.png)", "two.md"),
+    ];
+    let toml = "split-by-chapter = true\ncounter-offsets = { image = 3 }";
+    let book = render(Path::new("."), toml, items).unwrap();
+    // the offset is set once before the chapters, which don't reset the
+    // counters, so the figures are numbered 4 and 5
+    let offset = "#counter(figure.where(kind: image)).update(3)\n";
+    let first = book.main.find("#include \"01-one.typ\"").unwrap();
+    assert!(book.main.find(offset).unwrap() < first, "{}", book.main);
+    assert!(book.main[first..].contains("#include \"02-two.typ\""));
+    for (name, typ) in &book.files[1..] {
+        assert!(!typ.contains("#counter"), "{name:?}: {typ}");
+        assert!(typ.contains("#figure(image("), "{name:?}: {typ}");
+    }
+}
+
+#[test]
+fn appendix_numbering() {
+    let items = || {
+        vec![
+            chapter("One", &[1], "Text", "one.md"),
+            BookItem::PartTitle("Appendices".to_string()),
+            chapter("Data", &[2], "Text", "data.md"),
+            BookItem::PartTitle("Extras".to_string()),
+            chapter("More", &[3], "Text", "more.md"),
+        ]
+    };
+    let typ = render(Path::new("."), "appendix-numbering = \"I.1\"", items())
+        .unwrap()
+        .main;
+    let appendix =
+        "<part:appendices>\n#[\n#set heading(numbering: \"I.1\")\n#counter(heading).update(0)\n";
+    assert!(typ.contains(appendix), "{typ}");
+    // the numbering is kept to a block that ends before the next part
+    let start = typ.find("<part:appendices>").unwrap();
+    let extras = typ.find("<part:extras>").unwrap();
+    assert!(typ[start..extras].contains("\n]\n"), "{typ}");
+    assert!(!typ[extras..].contains("#set heading"), "{typ}");
+    // the appendices are lettered by default, up to the end of the book
+    let items = items().into_iter().take(3).collect();
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    assert!(typ.contains("#set heading(numbering: \"A.1\")"), "{typ}");
+    assert!(typ.trim_end().ends_with("\n]"), "{typ}");
+}
+
+#[test]
+fn code_comment_titles() {
+    let md = "# Setup\n\n```sh\n# install it\nmake\n```\n";
+    let fenced = "```sh\n# a comment first\n```\n\nText\n";
+    let items = vec![
+        chapter("Setup", &[1], md, "setup.md"),
+        chapter("Fenced", &[2], fenced, "fenced.md"),
+    ];
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    // the comment isn't a second title, so the first one is the chapter title
+    assert!(!typ.contains("== Setup"), "{typ}");
+    assert!(typ.contains("\n# install it\nmake\n"), "{typ}");
+    // a chapter starting with a code block keeps it
+    assert!(typ.contains("``````sh\n# a comment first\n``````"), "{typ}");
+}
+
+#[test]
+fn changelog() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("CHANGES.md"), "- Fixed the *typos*\n").unwrap();
+    let items = || vec![chapter("Intro", &[1], "Text", "intro.md")];
+    let typ = render(root.path(), "changelog = \"CHANGES.md\"", items())
+        .unwrap()
+        .main;
+    // the changelog is the last chapter, after the book contents
+    let intro = typ.find("Text").unwrap();
+    let changes = typ.find("- Fixed the _typos_").unwrap();
+    assert!(intro < changes, "{typ}");
+    assert!(
+        typ[intro..changes].contains("#pagebreak(weak: true)"),
+        "{typ}"
+    );
+    let err = render(root.path(), "changelog = \"missing.md\"", items()).unwrap_err();
+    assert!(err.to_string().contains("failed to read the changelog"));
+}
+
+#[test]
+fn special_names() {
+    let items = vec![
+        BookItem::PartTitle("Part #1 @ $5".to_string()),
+        chapter("C# <tags> [and] @me", &[1], "Text", "csharp.md"),
+    ];
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    assert!(typ.contains("bookpart()[Part \\#1 \\@ \\$5]"), "{typ}");
+    assert!(
+        typ.contains("#heading(level:1)[C\\# \\<tags\\> \\[and\\] \\@me]"),
+        "{typ}"
+    );
+}
+
+#[test]
+fn separator_marker() {
+    let items = || {
+        vec![
+            chapter("One", &[1], "Text", "one.md"),
+            BookItem::Separator,
+            chapter("Two", &[2], "Text", "two.md"),
+        ]
+    };
+    let typ = render(Path::new("."), "separator-marker = true", items())
+        .unwrap()
+        .main;
+    assert!(
+        typ.contains("#pagebreak(weak: true)\n#metadata(\"separator\") <separator>\n"),
+        "{typ}"
+    );
+    let typ = render(Path::new("."), "", items()).unwrap().main;
+    assert!(typ.contains("#pagebreak(weak: true)"), "{typ}");
+    assert!(!typ.contains("<separator>"), "{typ}");
+}
+
+#[test]
+fn split_subdirectory() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(root.path().join("src/guide/img")).unwrap();
+    std::fs::write(root.path().join("src/guide/img/a.png"), "png").unwrap();
+    let items = vec![chapter("Setup", &[1], "![](img/a.png)", "guide/setup.md")];
+    let toml = "split-by-chapter = true\nchapter-filename-template = \"chapters/{slug}.typ\"";
+    let book = render(root.path(), toml, items).unwrap();
+    let (name, typ) = &book.files[1];
+    assert_eq!(name, Path::new("chapters/setup.typ"));
+    assert!(typ.starts_with("#import \"../prelude.typ\": *\n"), "{typ}");
+    // the image path is from the root, not from the chapter file
+    assert!(typ.contains("image(\"/guide/img/a.png\")"), "{typ}");
+    assert!(root.path().join("book/guide/img/a.png").exists());
+}
+
+#[test]
+fn leading_blocks() {
+    let items = vec![
+        chapter("List", &[1], "- one\n- two\n\n## Sub\n", "list.md"),
+        chapter("Table", &[2], "| A |\n|---|\n| 1 |\n", "table.md"),
+        chapter("Code", &[3], "```sh\nmake\n```\n", "code.md"),
+    ];
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    // the content follows the title, and keeps the level of the chapter
+    assert!(
+        typ.contains("<chapter:list>\n\n- one\n- two\n\n=== Sub <list:sub>\n"),
+        "{typ}"
+    );
+    assert!(
+        typ.contains("<chapter:table>\n\n#table(\n  columns: 1,\n"),
+        "{typ}"
+    );
+    assert!(
+        typ.contains("<chapter:code>\n\n``````sh\nmake\n``````\n"),
+        "{typ}"
+    );
+}
+
+#[test]
+fn path_labels() {
+    let a = "## Setup\n\nSee [the other](../b/README.md#setup) and [b](../b/README.md).";
+    let items = vec![
+        chapter("A", &[1], a, "a/README.md"),
+        chapter("B", &[2], "## Setup\n", "b/README.md"),
+    ];
+    // the labels of the chapters with the same file name differ
+    let typ = render(Path::new("."), "strict-labels = true", items)
+        .unwrap()
+        .main;
+    assert!(typ.contains("[A] <chapter:a_readme>"), "{typ}");
+    assert!(typ.contains("[B] <chapter:b_readme>"), "{typ}");
+    assert!(typ.contains("=== Setup <a_readme:setup>"), "{typ}");
+    assert!(typ.contains("=== Setup <b_readme:setup>"), "{typ}");
+    // and the links are found from the chapter they are in
+    assert!(
+        typ.contains("See #link(<b_readme:setup>)[the other] and #link(<chapter:b_readme>)[b]."),
+        "{typ}"
+    );
+}
+
+#[test]
+fn reset_page_numbering() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("front.typ"), "= Preface").unwrap();
+    let items = || vec![chapter("Intro", &[1], "Text", "intro.md")];
+    let typ = |toml: &str| render(root.path(), toml, items()).unwrap().main;
+    let reset = "#counter(page).update(1)\n#set page(numbering: \"1\")\n";
+    // the contents are numbered from 1 after the outline
+    let without = typ("");
+    assert_eq!(without.matches(reset).count(), 1, "{without}");
+    assert!(without.find(reset) > without.find("#outline"), "{without}");
+    // or after the front matter, which is numbered like the outline
+    let with = typ("front-matter = \"front.typ\"");
+    assert_eq!(with.matches(reset).count(), 1, "{with}");
+    assert!(with.find(reset) > with.find("= Preface"), "{with}");
+    // without the reset all the pages are numbered in one sequence
+    for toml in ["", "front-matter = \"front.typ\"\n"] {
+        let typ = typ(&format!("{toml}reset-page-numbering = false"));
+        assert!(!typ.contains("#counter(page)"), "{typ}");
+        assert!(
+            typ.contains("#set page(numbering: \"1\")\n#outline"),
+            "{typ}"
+        );
+    }
+}
+
+#[test]
+fn part_title_markdown() {
+    let items = vec![
+        BookItem::PartTitle("Using `#set` with **C#** & a < b // ~~old~~".to_string()),
+        chapter("Intro", &[1], "Text", "intro.md"),
+    ];
+    let typ = render(Path::new("."), "", items).unwrap().main;
+    assert!(
+        typ.contains("bookpart()[Using `#set` with #strong[C\\#] & a \\< b \\// #strike[old]]"),
+        "{typ}"
+    );
+}
@@ -0,0 +1,212 @@
+//! Runs of the renderer binary the way mdbook runs it, with the render
+//! context on its stdin
+
+use mdbook_renderer::RenderContext;
+use mdbook_renderer::book::{Book, BookItem, Chapter, SectionNumber};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/// A numbered chapter with the file `path` in the book source
+fn chapter(name: &str, number: &[u32], content: &str, path: &str) -> BookItem {
+    let mut chap = Chapter::new(name, content.to_string(), path, Vec::new());
+    chap.number = Some(SectionNumber::new(number));
+    BookItem::Chapter(chap)
+}
+
+/// Run the renderer on the book of `items`, built in `root` with the
+/// `[output.typst]` options in `toml`
+fn run_book(root: &Path, toml: &str, items: Vec<BookItem>) -> Output {
+    let book = Book::new_with_items(items);
+    let config = format!("[output.typst]\n{toml}").parse().unwrap();
+    let ctx = RenderContext::new(root, book, config, root.join("book"));
+    run_with(&serde_json::to_vec(&ctx).unwrap())
+}
+
+/// Run the renderer on a book with a single chapter, built in `root`
+/// with the `[output.typst]` options in `toml`
+fn run(root: &Path, toml: &str, content: &str) -> Output {
+    run_book(
+        root,
+        toml,
+        vec![chapter("Intro", &[1], content, "intro.md")],
+    )
+}
+
+/// Run the renderer with `stdin` as its input
+fn run_with(stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mdbook-typst"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// The typst written for the book of `items`
+fn render_book(items: Vec<BookItem>) -> String {
+    let root = tempfile::tempdir().unwrap();
+    let output = run_book(root.path(), "", items);
+    assert!(output.status.success(), "{output:?}");
+    std::fs::read_to_string(root.path().join("book/book.typ")).unwrap()
+}
+
+/// The typst written for a book with the single chapter `md`
+fn render(md: &str) -> String {
+    render_book(vec![chapter("Chapter", &[1], md, "chapter.md")])
+}
+
+#[test]
+fn two_footnotes() {
+    let md =
+        "[^before]: Defined first.\n\nOne[^before] and two.[^after]\n\n[^after]: Defined after.\n";
+    let typ = render(md);
+    assert!(typ.contains("One#footnote[Defined first.]"), "{typ}");
+    assert!(typ.contains("two.#footnote[Defined after.]"), "{typ}");
+    // the definitions are only shown at their references
+    assert_eq!(typ.matches("Defined").count(), 2, "{typ}");
+}
+
+#[test]
+fn numbered_under_unnumbered() {
+    let mut parent = Chapter::new("Notes", "Text".to_string(), "notes.md", Vec::new());
+    parent
+        .sub_items
+        .push(chapter("Child", &[2, 1], "More", "child.md"));
+    let typ = render_book(vec![BookItem::Chapter(parent)]);
+    assert!(typ.contains("#unum_chap()[Notes]"), "{typ}");
+    // the child has its own number, so it is numbered at its level
+    assert!(typ.contains("#heading(level:2)[Child]"), "{typ}");
+}
+
+#[test]
+fn five_backtick_run() {
+    let typ = render("``````markdown\n`````\nin the code\n`````\n``````\n");
+    // the fence is longer than the run, so the run stays in the code
+    assert!(
+        typ.contains("\n``````markdown\n`````\nin the code\n`````\n``````\n"),
+        "{typ}"
+    );
+    let typ = render("````````\n``````\n````````\n");
+    assert!(typ.contains("\n```````\n``````\n```````\n"), "{typ}");
+}
+
+#[test]
+fn task_list() {
+    let typ = render("- [x] done\n- [ ] todo\n");
+    // the text follows the box on the same line
+    assert!(typ.contains("- ☒ done\n- ☐ todo\n"), "{typ}");
+}
+
+#[test]
+fn list_start() {
+    let typ = render("5. five\n6. six\n7. seven\n");
+    assert!(typ.contains("5. five\n6. six\n7. seven\n"), "{typ}");
+    // the numbers go on from the first one, whatever the markdown has
+    let typ = render("5. five\n1. six\n1. seven\n");
+    assert!(typ.contains("5. five\n6. six\n7. seven\n"), "{typ}");
+    let typ = render("- one\n- two\n");
+    assert!(typ.contains("- one\n- two\n"), "{typ}");
+}
+
+#[test]
+fn write_failure() {
+    let root = tempfile::tempdir().unwrap();
+    // a directory where the file goes can't be written over
+    std::fs::create_dir_all(root.path().join("book/book.typ")).unwrap();
+    let output = run(root.path(), "", "Text");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("failed to write"), "{stderr}");
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
+
+#[test]
+fn output_file() {
+    let root = tempfile::tempdir().unwrap();
+    let output = run(root.path(), "output-file = \"report.typ\"", "Text");
+    assert!(output.status.success(), "{output:?}");
+    let typ = std::fs::read_to_string(root.path().join("book/report.typ")).unwrap();
+    assert!(typ.contains("Text"));
+    assert!(!root.path().join("book/book.typ").exists());
+    // the file has to stay in the build directory
+    let absolute = root.path().join("report.typ");
+    for path in [absolute.as_path(), Path::new("../report.typ")] {
+        let output = run(root.path(), &format!("output-file = {path:?}"), "Text");
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("must be a relative path inside the build directory"),
+            "{stderr}"
+        );
+    }
+    assert!(!root.path().join("report.typ").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn compile_pdf_root() {
+    use std::os::unix::fs::PermissionsExt;
+    let root = tempfile::tempdir().unwrap();
+    // a typst that writes its arguments instead of compiling
+    let typst = root.path().join("typst");
+    std::fs::write(&typst, "#!/bin/sh\necho \"$@\" > args.txt\n").unwrap();
+    std::fs::set_permissions(&typst, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let toml = format!("compile-pdf = true\noutput-file = \"out/book.typ\"\ntypst-bin = {typst:?}");
+    let output = run(root.path(), &toml, "![](img/a.png)");
+    assert!(output.status.success(), "{output:?}");
+    let args = std::fs::read_to_string(root.path().join("book/args.txt")).unwrap();
+    // the images are found from the build directory, not the file's one
+    assert_eq!(args, "compile out/book.typ out/book.pdf --root .\n");
+}
+
+#[test]
+fn paper_warning() {
+    let root = tempfile::tempdir().unwrap();
+    let warns = |paper: &str| {
+        let output = run(root.path(), &format!("paper = {paper:?}"), "Text");
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stderr).contains("may not be a typst paper size")
+    };
+    assert!(!warns("a11"));
+    assert!(!warns("iso-b5"));
+    assert!(!warns("iso-c5"));
+    // typst only has the B and C series with their `iso-` prefix
+    assert!(warns("b5"));
+    assert!(warns("c5"));
+}
+
+#[test]
+fn invalid_input() {
+    let output = run_with(b"{ not json");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("failed to parse the RenderContext"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
+
+#[test]
+fn repeated_label() {
+    let root = tempfile::tempdir().unwrap();
+    let md = "## Intro\n\n## Overview { #intro }\n";
+    let output = run(root.path(), "", md);
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Warning: the label <intro:intro> of \"Intro\" is also used"),
+        "{stderr}"
+    );
+    // or the build fails with strict-labels
+    let output = run(root.path(), "strict-labels = true", md);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("labels used more than once: intro:intro"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,56 @@
+//! The emitter module, and custom emitters replacing the markup of some
+//! elements
+
+use mdbook_typst::emitter::{CodeInfo, TypstEmitter, fence_len};
+use mdbook_typst::{RenderOptions, render_markdown_with};
+
+/// Images without the figure around them
+struct PlainImages;
+
+impl TypstEmitter for PlainImages {
+    fn image(
+        &self,
+        path: &str,
+        _width: Option<&str>,
+        _alt: Option<&str>,
+        _caption: &str,
+    ) -> String {
+        format!("\n#image({path})\n")
+    }
+}
+
+#[test]
+fn custom_image() {
+    let md = "## Diagram\n\n![A diagram](img/diagram.png)\n";
+    let opts = RenderOptions {
+        level: 1,
+        numbered: true,
+        ..Default::default()
+    };
+    let typ = render_markdown_with(md, opts, &PlainImages);
+    assert!(typ.contains("\n#image(\"/img/diagram.png\")\n"));
+    assert!(!typ.contains("#figure"));
+    // the other elements keep the default markup
+    assert!(typ.contains("=== Diagram"));
+}
+
+#[test]
+fn code_info() {
+    let info = CodeInfo::parse("rust {#lst:example .numberLines .wide}");
+    assert_eq!(info.lang, "rust");
+    assert_eq!(info.id.as_deref(), Some("lst:example"));
+    assert_eq!(info.classes, ["numberLines", "wide"]);
+    assert!(info.number_lines());
+    // the mdbook flags after the language are not a part of it
+    let info = CodeInfo::parse("rust,ignore");
+    assert_eq!(info.lang, "rust");
+    assert_eq!(info.id, None);
+    assert!(!info.number_lines());
+}
+
+#[test]
+fn fence_length() {
+    assert_eq!(fence_len("no backticks"), 6);
+    // the fence is longer than the longest run in the code
+    assert_eq!(fence_len("```````\nseven\n```````"), 8);
+}
@@ -0,0 +1,56 @@
+//! Snapshot tests of the markdown in `tests/fixtures`
+//!
+//! Each `name.md` is rendered as a numbered chapter and compared with
+//! `name.typ`. Run with `UPDATE_SNAPSHOTS=1` to write the snapshots
+//! after a change in the output, and review their diff. A `name.toml`
+//! next to the markdown has the `[output.typst]` options it is rendered
+//! with, for the features that are off by default.
+
+use mdbook_typst::config::Config;
+use mdbook_typst::{RenderOptions, render_markdown_to_string};
+use std::path::{Path, PathBuf};
+
+fn render(path: &Path) -> String {
+    let md = std::fs::read_to_string(path).unwrap();
+    let config: Config = match std::fs::read_to_string(path.with_extension("toml")) {
+        Ok(toml) => toml::from_str(&toml).unwrap(),
+        Err(_) => Config::default(),
+    };
+    let opts = RenderOptions {
+        config,
+        level: 1,
+        numbered: true,
+        path: path.file_name().map(PathBuf::from),
+        ..Default::default()
+    };
+    render_markdown_to_string(&md, opts)
+}
+
+#[test]
+fn fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut fixtures: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "md"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty());
+
+    let mut failed = Vec::new();
+    for md in fixtures {
+        let typ = render(&md);
+        let snapshot = md.with_extension("typ");
+        if update {
+            std::fs::write(&snapshot, &typ).unwrap();
+            continue;
+        }
+        let expected = std::fs::read_to_string(&snapshot).unwrap_or_default();
+        if typ != expected {
+            eprintln!("--- {snapshot:?}\n{expected}\n+++ rendered\n{typ}");
+            failed.push(md.file_name().unwrap().to_string_lossy().to_string());
+        }
+    }
+    assert!(failed.is_empty(), "output changed for {failed:?}");
+}
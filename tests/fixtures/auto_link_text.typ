@@ -0,0 +1,5 @@
+
+
+
+See #ref(<setup:install>), #ref(<chapter:setup>) and #link(<setup:steps>)[the steps].
+
@@ -0,0 +1,6 @@
+
+````````markdown
+```````
+a block fenced with seven backticks
+```````
+````````
@@ -0,0 +1,13 @@
+
+#quote(block: true)[
+A quote
+over two lines
+]
+
+#htmlblock("warning")[
+
+
+
+be careful
+
+]
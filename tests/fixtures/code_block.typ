@@ -0,0 +1,15 @@
+
+``````rust
+fn main() {
+    println!("hi");
+}
+``````
+
+``````
+no language
+``````
+
+``````sh
+!important
+ls !(*.md)
+``````
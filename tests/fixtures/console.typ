@@ -0,0 +1,13 @@
+
+#[
+#show raw.line: it => if it.text.starts-with("$ ") or it.text.starts-with("> ") { strong(it.body) } else { it }
+``````console
+$ cargo build
+   Compiling thing v0.1.0
+> continued
+``````
+]
+
+``````sh
+$ not a console block
+``````
@@ -0,0 +1,15 @@
+
+== Windows <crlf:windows>
+
+
+
+A paragraph
+over two lines.
+
+
+``````sh
+make
+``````
+
+- one
+- two
@@ -0,0 +1,13 @@
+#htmlblock("details")[*▸ Closed*\
+
+
+
+Hidden text.
+
+]*Open*
+
+
+
+
+Shown text.
+
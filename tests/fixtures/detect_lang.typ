@@ -0,0 +1,11 @@
+
+``````rust
+fn main() {
+    let x = 1;
+    println!("{x}");
+}
+``````
+
+``````
+just some words
+``````
@@ -0,0 +1,11 @@
+
+
+
+One
+
+Two
+
+ 
+
+Three
+
@@ -0,0 +1,5 @@
+
+
+
+\*not bold\*, \#not a function, \_not emphasis\_ and \`not code\`.
+
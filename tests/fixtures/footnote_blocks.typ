@@ -0,0 +1,15 @@
+
+
+
+A note with blocks.#footnote[
+It has a list:
+
+
+- one
+- two
+
+``````rust
+let x = 1;
+``````
+] <fn:footnote_blocks:blocks>
+
@@ -0,0 +1,7 @@
+
+
+
+A note defined right after its paragraph.#footnote[The near note.] <fn:footnotes:near>
+
+A note defined at the end,#footnote[The far note.] <fn:footnotes:far> and the same one again.#footnote(<fn:footnotes:far>)
+
@@ -0,0 +1,7 @@
+
+#table(
+  columns: 2,
+  align: (auto, right),
+  table.header([*Name*], [*Value*])
+)
+
@@ -0,0 +1,13 @@
+
+== Chapter <heading_label:chapter>
+
+=== A heading <heading_label:custom>
+
+=== Another heading <heading_label:another-heading>
+
+
+
+Text with _emphasis_, *strong* and `code`.
+
+
+=== Spaced id <heading_label:my_label>
@@ -0,0 +1,14 @@
+
+
+
+Some #highlight[highlighted text] here, but a==b and #highlight[hi] there.
+
+A comparison a == b stays.
+
+
+== A heading underline <highlight:a-heading-underline>
+
+
+
+Another #highlight[one] after it.
+
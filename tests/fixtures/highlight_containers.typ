@@ -0,0 +1,17 @@
+
+#table(
+  columns: 2,
+  table.header([*A*], [*B*]),
+  [==x], [y], [#highlight[a]], [b]
+)
+
+
+- ==open item
+- next
+
+
+
+Later para.
+
+
+- #highlight[one] and _#highlight[two]_
@@ -0,0 +1,4 @@
+
+#figure(image("/img/chart.png", width: 50%, alt: "A chart"), caption: [The #emph[sales] of `2024` in \$])
+
+#figure(image("/img/map.png"), caption: [A #strong[map] over lines])
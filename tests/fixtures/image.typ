@@ -0,0 +1,15 @@
+
+
+
+
+#figure(image("/img/diagram.png", alt: "A diagram"), caption: [A diagram])
+
+
+
+#figure(image("/img/plain.png"), caption: [With a title])
+
+
+
+#figure(image("/img/bare.png"))
+
+
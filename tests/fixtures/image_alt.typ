@@ -0,0 +1,7 @@
+
+
+
+
+#figure(image("/img/x.png", alt: "important diagram with code"), caption: [#emph[important] diagram with `code`])
+
+
@@ -0,0 +1,14 @@
+
+=== Intro <label_collision:intro>
+
+
+
+The start.
+
+
+=== Overview <label_collision:intro>
+
+
+
+Also the start.
+
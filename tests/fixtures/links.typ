@@ -0,0 +1,7 @@
+
+
+
+See #link("https://example.com")[https://example.com], #link("https://example.org")[https://example.org], #link(<chapter:intro>)[the intro] and #link(<other:section>)[a section].
+
+A link to #link(<heading_label:my-label>)[a spaced id].
+
@@ -0,0 +1,24 @@
+
+- one
+- two
+
+
+
+A paragraph after the list.
+
+
+1. first
+
+
+  still in the first
+
+
+2. second
+  Lazy text of the second.
+
+
+
+
+
+The end.
+
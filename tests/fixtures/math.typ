@@ -0,0 +1,7 @@
+
+
+
+The area is $pi r^2$ and costs \$5.
+
+$ sum_(i=1)^n i = (n(n+1))/2 $
+
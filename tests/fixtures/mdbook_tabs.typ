@@ -0,0 +1,19 @@
+
+
+
+Install it with:
+
+
+*Linux*
+
+
+``````sh
+apt install thing
+``````
+*Windows*
+
+
+
+
+Run the _installer_.
+
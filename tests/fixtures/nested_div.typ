@@ -0,0 +1,21 @@
+#htmlblock("outer")[
+
+
+Outer text.
+
+#htmlblock("inner")[
+
+
+Inner text.
+
+]
+
+
+No class.
+
+
+
+
+Back in the outer.
+
+]
\ No newline at end of file
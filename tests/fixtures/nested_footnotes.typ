@@ -0,0 +1,7 @@
+
+
+
+Some text.#footnote[See also #footnote[The other note.] <fn:nested_footnotes:b>.] <fn:nested_footnotes:a> More text.#footnote(<fn:nested_footnotes:b>)
+
+A loop.#footnote[Back to #footnote[And to #footnote(<fn:nested_footnotes:c>).] <fn:nested_footnotes:d>.] <fn:nested_footnotes:c>
+
@@ -0,0 +1,23 @@
+
+- one
+- two
+  wrapped
+  - nested
+
+
+  - nested again
+
+
+    second paragraph
+
+
+
+- three
+
+1. first
+2. second
+
+
+
+Paragraph after the list.
+
@@ -0,0 +1,8 @@
+
+1. Prepare
+  - ☒ buy the flour
+  - ☐ heat the oven
+
+2. Bake
+  - ☐ wait
+
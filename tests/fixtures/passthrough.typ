@@ -0,0 +1,9 @@
+
+== Typst <passthrough:typst>
+
+#rect(width: 2cm)[!not hidden]
+
+
+``````typst
+#rect()
+``````
@@ -0,0 +1,17 @@
+
+#quote(block: true, attribution: [Edsger W. _Dijkstra_])[
+Simplicity is prerequisite for reliability.
+]
+
+#quote(block: true)[
+A quote without one.
+]
+
+#quote(block: true)[
+Outer
+
+
+#quote(block: true, attribution: [Someone])[
+Inner quote
+]
+]
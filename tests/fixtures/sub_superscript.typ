@@ -0,0 +1,7 @@
+
+
+
+Energy is E=mc#super[2] and the gas is CO#sub[2].
+
+A lone \~ and a ^ stay, as does #strike[struck] text.
+
@@ -0,0 +1,10 @@
+
+#figure(kind: table, caption: [The values])[
+#table(
+  columns: 2,
+  align: (left, right),
+  table.header([*Name*], [*Value*]),
+  [a], [1], [_b_], [2]
+)
+
+]
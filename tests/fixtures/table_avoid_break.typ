@@ -0,0 +1,12 @@
+
+#block(breakable: false)[
+#figure(kind: table, caption: [The steps])[
+#table(
+  columns: 2,
+  align: (auto, right),
+  table.header([*Step*], [*Time*]),
+  [Mix], [5 min], [Bake], [40 min]
+)
+
+]
+]
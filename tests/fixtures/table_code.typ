@@ -0,0 +1,7 @@
+
+#table(
+  columns: 2,
+  table.header([*Syntax*], [*Shows*]),
+  [#raw("`` `code` ``")], [#raw("`code`")], [`plain`], [text]
+)
+
@@ -0,0 +1,7 @@
+
+#table(
+  columns: 2,
+  table.header([*Nested*], [*Plain*]),
+  [*#emph[x]*], [_a #strong[b] c_], [_one_], [*two*]
+)
+
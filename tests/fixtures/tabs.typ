@@ -0,0 +1,5 @@
+
+
+
+Tabs#h(2em)in#h(2em)a paragraph.
+
@@ -0,0 +1,6 @@
+
+- ☒ #strike[Write the _draft_]
+- ☐ Review it
+- ☒ #strike[Pick a title]
+  - ☐ a sub-task
+
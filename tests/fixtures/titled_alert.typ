@@ -0,0 +1,18 @@
+
+#htmlblock("note")[
+
+
+
+*My _own_ title*
+
+The note text.
+
+]
+
+#htmlblock("tip")[
+
+
+
+An untitled tip.
+
+]
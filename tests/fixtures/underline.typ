@@ -0,0 +1,5 @@
+
+
+
+Some #underline[underlined] text, #underline[with _emphasis_ inside].
+
@@ -0,0 +1,253 @@
+//! The rules the options add to the default prelude
+
+use mdbook_renderer::config::BookConfig;
+use mdbook_typst::config::Config;
+use std::path::Path;
+
+fn prelude(cfg: Config) -> String {
+    cfg.prelude(Path::new("."), &BookConfig::default()).unwrap()
+}
+
+#[test]
+fn table_font_size() {
+    let cfg = Config {
+        table_font_size: Some("9pt".to_string()),
+        ..Default::default()
+    };
+    assert!(prelude(cfg).contains("#show table: set text(size: 9pt)\n"));
+    assert!(!prelude(Config::default()).contains("#show table"));
+}
+
+#[test]
+fn title_page() {
+    let mut book = BookConfig::default();
+    book.title = Some("A \"Book\"".to_string());
+    book.authors = vec!["Someone".to_string()];
+    let with_title = |cfg: Config| cfg.prelude(Path::new("."), &book).unwrap();
+    let typ = with_title(Config::default());
+    assert!(typ.contains("#set document(title: \"A \\\"Book\\\"\", author: (\"Someone\",))\n"));
+    assert!(typ.contains("#page(numbering: none"));
+    let typ = with_title(Config {
+        title_page: Some(false),
+        ..Default::default()
+    });
+    assert!(!typ.contains("#set document"));
+    assert!(!typ.contains("#page("));
+    // no title page without a title
+    assert!(!prelude(Config::default()).contains("#page("));
+}
+
+#[test]
+fn code_lang_badge() {
+    let cfg = Config {
+        code_lang_badge: true,
+        ..Default::default()
+    };
+    assert!(prelude(cfg).contains("upper(it.lang)"));
+    assert!(!prelude(Config::default()).contains("upper(it.lang)"));
+}
+
+#[test]
+fn prelude_mode() {
+    use mdbook_typst::config::PreludeMode;
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("prelude.typ"), "// file").unwrap();
+    let with_mode = |mode: Option<PreludeMode>| {
+        let cfg = Config {
+            prelude: Some("prelude.typ".into()),
+            prelude_str: Some("// str".to_string()),
+            prelude_mode: mode,
+            ..Default::default()
+        };
+        cfg.prelude(root.path(), &BookConfig::default()).unwrap()
+    };
+    // without a mode the string is used
+    assert_eq!(with_mode(None), "// str");
+    assert_eq!(with_mode(Some(PreludeMode::File)), "// file");
+    assert_eq!(with_mode(Some(PreludeMode::Str)), "// str");
+    assert_eq!(with_mode(Some(PreludeMode::Both)), "// file\n// str");
+    // and the file when it is the only one
+    let cfg = Config {
+        prelude: Some("prelude.typ".into()),
+        ..Default::default()
+    };
+    let typ = cfg.prelude(root.path(), &BookConfig::default()).unwrap();
+    assert_eq!(typ, "// file");
+}
+
+#[test]
+fn front_matter_numbering() {
+    let cfg = Config {
+        front_matter_numbering: Some("I".to_string()),
+        ..Default::default()
+    };
+    let typ = prelude(cfg);
+    assert!(typ.contains("#set page(numbering: \"I\")\n#counter(page).update(1)\n"));
+    // the contents are numbered in arabic after the outline
+    assert!(typ.ends_with("#counter(page).update(1)\n#set page(numbering: \"1\")\n"));
+    assert!(prelude(Config::default()).contains("#set page(numbering: \"i\")"));
+}
+
+#[test]
+fn caption_position() {
+    use mdbook_typst::config::CaptionPosition;
+    let cfg = Config {
+        caption_position: Some(CaptionPosition::Above),
+        ..Default::default()
+    };
+    assert!(prelude(cfg).contains("#set figure.caption(position: top)\n"));
+    assert!(!prelude(Config::default()).contains("figure.caption"));
+}
+
+#[test]
+fn code_block_width() {
+    let cfg = Config {
+        code_block_width: Some("auto".to_string()),
+        ..Default::default()
+    };
+    assert!(
+        prelude(cfg).contains(
+            "#show raw: set block(fill: luma(230), inset: 8pt, radius: 4pt, width: auto)"
+        )
+    );
+    assert!(prelude(Config::default()).contains("radius: 4pt, width: 100%)"));
+}
+
+#[test]
+fn code_wrap() {
+    let cfg = Config {
+        code_wrap: true,
+        ..Default::default()
+    };
+    let rule = "#show raw.where(block: true): set par(justify: false, linebreaks: \"simple\")\n";
+    assert!(prelude(cfg).contains(rule));
+    assert!(!prelude(Config::default()).contains(rule));
+}
+
+#[test]
+fn link_style() {
+    let cfg = Config {
+        link_color: Some("blue".to_string()),
+        link_underline: Some(true),
+        ..Default::default()
+    };
+    let typ = prelude(cfg);
+    assert!(typ.contains("#show link: set text(fill: blue)\n"));
+    assert!(typ.contains("#show link: underline\n"));
+    assert!(!prelude(Config::default()).contains("#show link"));
+}
+
+#[test]
+fn heading_number_position() {
+    use mdbook_typst::config::NumberPosition;
+    let cfg = Config {
+        heading_number_position: Some(NumberPosition::Margin),
+        ..Default::default()
+    };
+    let typ = prelude(cfg);
+    assert!(typ.contains("place(dx: -3em, counter(heading).display(it.numbering))"));
+    // inline numbers are what typst does without a rule
+    let cfg = Config {
+        heading_number_position: Some(NumberPosition::Inline),
+        ..Default::default()
+    };
+    assert!(!prelude(cfg).contains("#show heading"));
+    assert!(!prelude(Config::default()).contains("#show heading"));
+}
+
+#[test]
+fn line_spacing() {
+    let with_spacing = |spacing: &str| {
+        let cfg = Config {
+            line_spacing: Some(spacing.to_string()),
+            ..Default::default()
+        };
+        prelude(cfg)
+    };
+    // a plain number is in the units of the font size
+    assert!(with_spacing("1.2").contains("leading: 1.2em, justify: true"));
+    assert!(with_spacing("6pt").contains("leading: 6pt, justify: true"));
+    assert!(prelude(Config::default()).contains("leading: .8em, justify: true"));
+}
+
+#[test]
+fn page_x_of_y() {
+    let cfg = Config {
+        page_x_of_y: true,
+        ..Default::default()
+    };
+    let footer = "#set page(footer: context align(center)[Page #counter(page).display() of #counter(page).final().first()])\n";
+    // the total is only counted after the reset of the contents
+    assert!(prelude(cfg).ends_with(&format!("#set page(numbering: \"1\")\n{footer}")));
+    assert!(!prelude(Config::default()).contains("footer"));
+}
+
+#[test]
+fn hyphenate() {
+    let with_hyphenate = |hyphenate| {
+        let cfg = Config {
+            hyphenate,
+            ..Default::default()
+        };
+        prelude(cfg)
+    };
+    assert!(with_hyphenate(Some(false)).contains("#set text(hyphenate: false)\n"));
+    assert!(with_hyphenate(Some(true)).contains("#set text(hyphenate: true)\n"));
+    // typst hyphenates the justified text without a rule
+    assert!(!with_hyphenate(None).contains("hyphenate"));
+}
+
+#[test]
+fn figure_supplement() {
+    let cfg = Config {
+        figure_supplement: Some("Abbildung".to_string()),
+        ..Default::default()
+    };
+    let rule = "#show figure.where(kind: image): set figure(supplement: [Abbildung])\n";
+    assert!(prelude(cfg).contains(rule));
+    assert!(!prelude(Config::default()).contains("supplement"));
+}
+
+#[test]
+fn missing_prelude() {
+    let cfg = |fallback| Config {
+        prelude: Some("missing.typ".into()),
+        prelude_fallback: fallback,
+        ..Default::default()
+    };
+    let err = cfg(false)
+        .prelude(Path::new("."), &BookConfig::default())
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    // the default prelude is used instead
+    assert_eq!(prelude(cfg(true)), prelude(Config::default()));
+}
+
+#[test]
+fn font() {
+    let cfg = Config {
+        font: Some("Linux Libertine".to_string()),
+        font_size: Some("12pt".to_string()),
+        ..Default::default()
+    };
+    assert!(prelude(cfg).contains("#set text(font: \"Linux Libertine\", size: 12pt)\n"));
+    // typst uses its own font without one
+    assert!(prelude(Config::default()).contains("#set text(size: 11pt)\n"));
+}
+
+#[test]
+fn tagged_pdf() {
+    let mut book = BookConfig::default();
+    book.title = Some("Book".to_string());
+    book.language = Some("de".to_string());
+    let cfg = Config {
+        tagged_pdf: true,
+        title_page: Some(false),
+        ..Default::default()
+    };
+    let typ = cfg.prelude(Path::new("."), &book).unwrap();
+    // the metadata is set even without the title page
+    assert!(typ.contains("#set document(title: \"Book\")\n"), "{typ}");
+    assert!(typ.contains("#set text(lang: \"de\")\n"), "{typ}");
+    assert!(!prelude(Config::default()).contains("lang:"));
+}
@@ -0,0 +1,378 @@
+//! Checks on the typst of single chapters, for what the snapshots in
+//! `tests/fixtures` don't show clearly
+
+use mdbook_typst::config::Config;
+use mdbook_typst::{RenderOptions, render_markdown_to_string};
+use std::path::PathBuf;
+
+fn render(md: &str, config: Config) -> String {
+    let opts = RenderOptions {
+        config,
+        level: 1,
+        numbered: true,
+        path: Some(PathBuf::from("chapter.md")),
+        ..Default::default()
+    };
+    render_markdown_to_string(md, opts)
+}
+
+#[test]
+fn image_paths() {
+    let opts = RenderOptions {
+        path: Some(["guide", "setup.md"].iter().collect()),
+        ..Default::default()
+    };
+    let typ = render_markdown_to_string("![](<img/a \"b\".png>)", opts);
+    // the separator is `/` on every platform, and the quote is escaped
+    assert!(typ.contains(r#"image("/guide/img/a \"b\".png")"#), "{typ}");
+}
+
+#[test]
+fn highlight_word_start() {
+    let cfg = Config {
+        pandoc_highlight: true,
+        ..Default::default()
+    };
+    let typ = render("hello a==b and ==hi== there", cfg);
+    assert!(typ.contains("hello a==b and #highlight[hi] there"), "{typ}");
+}
+
+#[cfg(unix)]
+#[test]
+fn converted_images() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    std::fs::create_dir_all(src.join("img")).unwrap();
+    std::fs::write(src.join("img/photo.webp"), "webp").unwrap();
+    std::fs::write(src.join("img/plot.png"), "png").unwrap();
+    let opts = RenderOptions {
+        config: Config {
+            image_convert: Some([("webp".to_string(), "png".to_string())].into()),
+            // copying is enough to stand in for the converter
+            image_converter: Some("cp".into()),
+            ..Default::default()
+        },
+        src_dir: src,
+        dest_dir: dir.path().join("book"),
+        ..Default::default()
+    };
+    let typ = render_markdown_to_string("![](img/photo.webp)\n\n![](img/plot.png)", opts);
+    // both images are in the build directory, and found from its root
+    assert!(typ.contains(r#"image("/img/photo.png")"#), "{typ}");
+    assert!(typ.contains(r#"image("/img/plot.png")"#), "{typ}");
+    let book = dir.path().join("book/img");
+    assert_eq!(
+        std::fs::read_to_string(book.join("photo.png")).unwrap(),
+        "webp"
+    );
+    assert_eq!(
+        std::fs::read_to_string(book.join("plot.png")).unwrap(),
+        "png"
+    );
+}
+
+#[test]
+fn bundle_assets() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    std::fs::create_dir_all(src.join("files")).unwrap();
+    std::fs::write(src.join("files/spec.pdf"), "pdf").unwrap();
+    let opts = RenderOptions {
+        config: Config {
+            bundle_assets: true,
+            ..Default::default()
+        },
+        src_dir: src,
+        dest_dir: dir.path().join("book"),
+        path: Some(["guide", "setup.md"].iter().collect()),
+        ..Default::default()
+    };
+    let md = "Read [the spec](../files/spec.pdf) or [the missing one](missing.pdf).";
+    let typ = render_markdown_to_string(md, opts);
+    assert!(
+        typ.contains(r#"#link("assets/files/spec.pdf")[the spec]"#),
+        "{typ}"
+    );
+    // a link to a file that isn't there stays as it is
+    assert!(
+        typ.contains(r#"#link("missing.pdf")[the missing one]"#),
+        "{typ}"
+    );
+    let copy = dir.path().join("book/assets/files/spec.pdf");
+    assert_eq!(std::fs::read_to_string(copy).unwrap(), "pdf");
+}
+
+#[test]
+fn slug_styles() {
+    use mdbook_typst::config::SlugStyle;
+    let md = "## 2.1 Über C++ & Rust  Guide\n\nSee [it](chapter.md#über-c--rust-guide).";
+    let label = |style| {
+        let cfg = Config {
+            slug_style: style,
+            ..Default::default()
+        };
+        let typ = render(md, cfg);
+        let start = typ.find("<chapter:").unwrap();
+        typ[start..start + typ[start..].find('>').unwrap() + 1].to_string()
+    };
+    assert_eq!(label(None), "<chapter:21-Über-c--rust--guide>");
+    assert_eq!(label(Some(SlugStyle::Mdbook)), label(None));
+    assert_eq!(
+        label(Some(SlugStyle::Github)),
+        "<chapter:21-über-c--rust--guide>"
+    );
+    assert_eq!(
+        label(Some(SlugStyle::Pandoc)),
+        "<chapter:über-c--rust-guide>"
+    );
+    // the links with the ids of the style point to the label
+    let cfg = Config {
+        slug_style: Some(SlugStyle::Pandoc),
+        ..Default::default()
+    };
+    assert!(render(md, cfg).contains("#link(<chapter:über-c--rust-guide>)[it]"));
+    // the ids of the links are normalized like the headings
+    let md = "## Über
+
+See [it](chapter.md#Über).";
+    for (style, label) in [
+        (None, "chapter:Über"),
+        (Some(SlugStyle::Mdbook), "chapter:Über"),
+        (Some(SlugStyle::Github), "chapter:über"),
+        (Some(SlugStyle::Pandoc), "chapter:über"),
+    ] {
+        let cfg = Config {
+            slug_style: style,
+            ..Default::default()
+        };
+        let typ = render(md, cfg);
+        assert!(typ.contains(&format!("== Über <{label}>")), "{typ}");
+        assert!(typ.contains(&format!("#link(<{label}>)[it]")), "{typ}");
+    }
+}
+
+#[test]
+fn footnote_placement() {
+    let near = "Text.[^a] More.[^a]\n\n[^a]: The note.\n\nAfter.";
+    let far = "Text.[^a] More.[^a]\n\nAfter.\n\n[^a]: The note.";
+    let typ = render(near, Config::default());
+    assert_eq!(typ, render(far, Config::default()));
+    // the note is shown once, the second use refers to it
+    assert_eq!(typ.matches("#footnote[The note.]").count(), 1, "{typ}");
+    assert!(typ.contains("#footnote(<fn:chapter:a>)"), "{typ}");
+}
+
+#[test]
+fn completed_task_style() {
+    use mdbook_typst::config::TaskStyle;
+    let md = "- [x] done\n- [ ] open\n";
+    let cfg = Config {
+        completed_task_style: Some(TaskStyle::Muted),
+        ..Default::default()
+    };
+    let typ = render(md, cfg);
+    assert!(typ.contains("- ☒ #text(fill: gray)[done]\n"), "{typ}");
+    assert!(typ.contains("- ☐ open\n"), "{typ}");
+    // without a style the checked items are left as they are
+    assert!(render(md, Config::default()).contains("- ☒ done\n"));
+}
+
+#[test]
+fn code_lang_caption() {
+    let md = "```rs\nfn main() {}\n```\n\n```\nplain\n```\n";
+    let cfg = Config {
+        code_lang_caption: true,
+        ..Default::default()
+    };
+    let typ = render(md, cfg);
+    assert!(
+        typ.contains("#figure(kind: raw, caption: [Rust])[\n``````rs\nfn main() {}\n``````"),
+        "{typ}"
+    );
+    // a block without a language has no caption
+    assert_eq!(typ.matches("#figure").count(), 1, "{typ}");
+    assert!(!render(md, Config::default()).contains("#figure"));
+}
+
+#[test]
+fn old_typst_version() {
+    let md = "Some ==marked== text\n\n| A | B |\n|---|---|\n| 1 | 2 |\n\n![A chart](chart.png)\n";
+    let with_version = |version: Option<&str>| {
+        let cfg = Config {
+            pandoc_highlight: true,
+            typst_version: version.map(str::to_string),
+            ..Default::default()
+        };
+        render(md, cfg)
+    };
+    let typ = with_version(Some("0.7"));
+    assert!(
+        typ.contains("Some #box(fill: yellow)[marked] text"),
+        "{typ}"
+    );
+    // no `table.header`, the header is the first row in bold
+    assert!(!typ.contains("table.header"), "{typ}");
+    assert!(typ.contains("[*A*], [*B*], [1], [2]"), "{typ}");
+    assert!(typ.contains("image(\"/chart.png\"), caption"), "{typ}");
+    assert!(!typ.contains("alt:"), "{typ}");
+    // the newer ones, and no version, have the newer functions
+    for version in [Some("0.11"), None] {
+        let typ = with_version(version);
+        assert!(typ.contains("Some #highlight[marked] text"), "{typ}");
+        assert!(typ.contains("table.header([*A*], [*B*])"), "{typ}");
+        assert!(
+            typ.contains("image(\"/chart.png\", alt: \"A chart\")"),
+            "{typ}"
+        );
+    }
+}
+
+#[test]
+fn table_avoid_break() {
+    let table = |rows: usize| "| A |\n|---|\n".to_string() + &"| x |\n".repeat(rows);
+    let cfg = || Config {
+        table_avoid_break: true,
+        ..Default::default()
+    };
+    assert!(render(&table(3), cfg()).contains("#block(breakable: false)["));
+    // a table longer than a page breaks anyway
+    assert!(!render(&table(31), cfg()).contains("#block(breakable: false)"));
+    assert!(!render(&table(3), Config::default()).contains("#block"));
+}
+
+#[test]
+fn escape_syntax() {
+    let typ = render(
+        "Use // for comments, /* or this */ and a~b",
+        Config::default(),
+    );
+    assert!(
+        typ.contains(r"Use \// for comments, \/\* or this \*/ and a\~b"),
+        "{typ}"
+    );
+    // the urls typst makes links of are left alone
+    let typ = render("See https://example.com here", Config::default());
+    assert!(typ.contains("See https://example.com here"), "{typ}");
+}
+
+#[test]
+fn escape_line_markers() {
+    let md = "First line\n2. not a list\n= not a heading\n/ not a term\n10.5 stays";
+    let typ = render(md, Config::default());
+    assert!(typ.contains("First line\n2\\. not a list\n"), "{typ}");
+    assert!(typ.contains("\n\\= not a heading\n"), "{typ}");
+    assert!(typ.contains("\n\\/ not a term\n"), "{typ}");
+    assert!(typ.contains("\n10.5 stays"), "{typ}");
+}
+
+#[test]
+fn crlf_line_endings() {
+    let typ = render(
+        "A line\r\nnext line\r\n\r\n```\r\ncode\r\n```\r\n",
+        Config::default(),
+    );
+    assert!(!typ.contains('\r'), "{typ:?}");
+    assert_eq!(
+        typ,
+        render("A line\nnext line\n\n```\ncode\n```\n", Config::default())
+    );
+}
+
+#[test]
+fn auto_link_text() {
+    let md = "See [](setup.md#install), [setup.md](setup.md) and [the steps](setup.md#steps).";
+    let cfg = Config {
+        auto_link_text: true,
+        ..Default::default()
+    };
+    assert!(render(md, cfg).contains(
+        "See #ref(<setup:install>), #ref(<chapter:setup>) and #link(<setup:steps>)[the steps]."
+    ));
+    // without it the links keep their text, even when it is empty
+    let typ = render(md, Config::default());
+    assert!(
+        typ.contains(
+            "See #link(<setup:install>)[], #link(<chapter:setup>)[setup.md] and #link(<setup:steps>)[the steps]."
+        ),
+        "{typ}"
+    );
+}
+
+#[test]
+fn rule_between_paragraphs() {
+    let typ = render("foo\n\n---\n\nbar", Config::default());
+    let rule = typ.find("#line(length: 100%)").expect(&typ);
+    assert!(typ[..rule].contains("foo"), "{typ}");
+    assert!(typ[rule..].contains("bar"), "{typ}");
+    // the rule is on its own line, not joined to the text around it
+    assert!(typ.contains("\n#line(length: 100%)\n"), "{typ}");
+}
+
+#[test]
+fn strikethrough() {
+    let typ = render("~~removed~~ kept", Config::default());
+    assert!(typ.contains("#strike[removed] kept"), "{typ}");
+}
+
+#[test]
+fn list_then_paragraph() {
+    let typ = render("- one\n- two\n\nAfter the list.", Config::default());
+    // a blank line ends the list, or typst would add the text to the item
+    let list = typ.find("- two\n").unwrap();
+    let text = typ.find("After the list.").unwrap();
+    assert!(typ[list + 6..text].trim().is_empty(), "{typ}");
+    assert!(typ[list..text].contains("\n\n"), "{typ}");
+    assert!(!typ[..text].ends_with("  "), "{typ}");
+}
+
+#[test]
+fn math() {
+    let md = "Inline $x^2$ and\n\n$$\nsum_(i=1)^n i\n$$\n";
+    let cfg = Config {
+        math: true,
+        ..Default::default()
+    };
+    let typ = render(md, cfg);
+    assert!(typ.contains("Inline $x^2$ and"), "{typ}");
+    // the spaces inside make it a display block in typst
+    assert!(typ.contains("$ sum_(i=1)^n i $"), "{typ}");
+    // without it the dollars are text
+    let typ = render(md, Config::default());
+    assert!(typ.contains("Inline \\$x^2\\$ and"), "{typ}");
+}
+
+#[test]
+fn sub_superscript() {
+    let cfg = || Config {
+        sub_superscript: true,
+        ..Default::default()
+    };
+    assert!(render("E=mc^2^", cfg()).contains("E=mc#super[2]"));
+    assert!(render("CO~2~", cfg()).contains("CO#sub[2]"));
+    // without it the marks are text
+    assert!(render("E=mc^2^ CO~2~", Config::default()).contains("E=mc^2^ CO\\~2\\~"));
+}
+
+#[test]
+fn heading_label_text() {
+    let typ = render("## A $x { #lbl }", Config::default());
+    assert!(typ.contains("=== A \\$x <chapter:lbl>"), "{typ}");
+}
+
+#[test]
+fn quote_attribution() {
+    let md = "> Be brief.\n>\n> — Someone\n";
+    let cfg = Config {
+        quote_attribution: true,
+        ..Default::default()
+    };
+    let typ = render(md, cfg);
+    assert!(
+        typ.contains("#quote(block: true, attribution: [Someone])[\nBe brief.\n]"),
+        "{typ}"
+    );
+    // without it the line stays in the quote
+    let typ = render(md, Config::default());
+    assert!(!typ.contains("attribution"), "{typ}");
+    assert!(typ.contains("— Someone"), "{typ}");
+}